@@ -40,6 +40,8 @@ in
         User = "raulyrs";
         ExecStart = lib.getExe cfg.package;
         EnvironmentFile = cfg.environmentFile;
+        StateDirectory = "raulyrs";
+        WorkingDirectory = "/var/lib/raulyrs";
         Restart = "on-failure";
       };
     };
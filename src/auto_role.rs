@@ -0,0 +1,81 @@
+use crate::db::Database;
+use crate::models::auto_role::AutoRole;
+use serenity::all::{CreateMessage, Mentionable};
+use serenity::http::{HttpError, StatusCode};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Gives a newly joined member every role their guild's rules call for.
+pub async fn assign(ctx: &Context, db: &Database, member: &Member) {
+    let rules = match db.auto_roles(member.guild_id) {
+        Ok(rules) => rules,
+        Err(e) => {
+            error!("Couldn't load auto roles for {}: {:#}", member.guild_id, e);
+            return;
+        }
+    };
+
+    for rule in rules
+        .into_iter()
+        .filter(|rule| rule.target.matches(member.user.bot))
+    {
+        let ctx = ctx.clone();
+        let user_id = member.user.id;
+        if rule.delay_secs == 0 {
+            add_role(&ctx, &rule, user_id).await;
+        } else {
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(rule.delay_secs)).await;
+                add_role(&ctx, &rule, user_id).await;
+            });
+        }
+    }
+}
+
+async fn add_role(ctx: &Context, rule: &AutoRole, user_id: UserId) {
+    let result = ctx
+        .http
+        .add_member_role(rule.guild_id, user_id, rule.role_id, Some("Auto role"))
+        .await;
+
+    match result {
+        Ok(_) => info!("added role {} to new member {}", rule.role_id, user_id),
+        Err(serenity::Error::Http(HttpError::UnsuccessfulRequest(e)))
+            if e.status_code == StatusCode::FORBIDDEN =>
+        {
+            warn!(
+                "Missing permission to give role {} in guild {}: {}",
+                rule.role_id, rule.guild_id, e.error.message
+            );
+            notify_missing_permission(ctx, rule, user_id).await;
+        }
+        Err(e) => error!("error adding role to member: {}", e),
+    }
+}
+
+/// Lets the server know through its system channel, since nobody reads the logs.
+async fn notify_missing_permission(ctx: &Context, rule: &AutoRole, user_id: UserId) {
+    let Some(channel_id) = ctx
+        .cache
+        .guild(rule.guild_id)
+        .and_then(|guild| guild.system_channel_id)
+    else {
+        return;
+    };
+
+    let content = format!(
+        "I couldn't give {} to {}. Make sure I have the **Manage Roles** permission \
+         and that my role is above {} in the role list.",
+        rule.role_id.mention(),
+        user_id.mention(),
+        rule.role_id.mention()
+    );
+    let msg = CreateMessage::new()
+        .content(content)
+        .allowed_mentions(Default::default());
+    if let Err(e) = channel_id.send_message(&ctx.http, msg).await {
+        warn!("Couldn't send auto role notice: {}", e);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::models::auto_role::{AutoRole, AutoRoleTarget};
+use crate::models::guild::Guild;
+use crate::{Context, Error};
+use chrono::{NaiveTime, Utc};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use serenity::all::{GuildChannel, Mentionable, Role};
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+
+/// Configure the bot for this server
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD",
+    subcommands(
+        "show",
+        "wotd_channel",
+        "wotd_time",
+        "timezone",
+        "autorole",
+        "music",
+        "dj_role"
+    ),
+    subcommand_required
+)]
+pub async fn config(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Show the current configuration of this server
+#[poise::command(slash_command, guild_only)]
+async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let rules = ctx.data().db.auto_roles(guild_id)?;
+    let embed = match ctx.data().guilds.get(guild_id).await {
+        Some(guild) => config_embed(&guild, &rules),
+        None => config_embed(&Guild::new(guild_id, guild_name(ctx)), &rules),
+    };
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Set the channel the Word of the Day is posted in
+#[poise::command(slash_command, guild_only)]
+async fn wotd_channel(
+    ctx: Context<'_>,
+    #[description = "Channel to post in, leave empty to stop posting"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+) -> Result<(), Error> {
+    let reply = match &channel {
+        Some(channel) => format!("Word of the Day will be posted in {}.", channel.mention()),
+        None => "Word of the Day will no longer be posted.".to_string(),
+    };
+    update_guild(ctx, |guild| {
+        guild.setup = channel.is_some();
+        guild.wotd_channel = channel.as_ref().map(|c| c.name.clone()).unwrap_or_default();
+        guild.wotd_channel_id = channel.as_ref().map(|c| c.id);
+        guild.wotd_configured_at = Some(Utc::now());
+    })
+    .await?;
+
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Set the time the Word of the Day is posted at
+#[poise::command(slash_command, guild_only)]
+async fn wotd_time(
+    ctx: Context<'_>,
+    #[description = "Local time in 24-hour HH:MM format"] time: String,
+) -> Result<(), Error> {
+    let Ok(time) = NaiveTime::parse_from_str(time.trim(), "%H:%M") else {
+        ctx.say("Time should look like `09:30` or `21:00`.").await?;
+        return Ok(());
+    };
+    let time = time.format("%H:%M").to_string();
+    update_guild(ctx, |guild| {
+        guild.wotd_time = time.clone();
+        guild.wotd_configured_at = Some(Utc::now());
+    })
+    .await?;
+
+    ctx.say(format!("Word of the Day will be posted at {}.", time))
+        .await?;
+    Ok(())
+}
+
+async fn autocomplete_timezone<'a>(
+    _ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = &'static str> + 'a {
+    let partial = partial.to_lowercase();
+    TZ_VARIANTS
+        .iter()
+        .map(|tz| tz.name())
+        .filter(move |name| name.to_lowercase().contains(&partial))
+        .take(25)
+}
+
+/// Set the timezone used for scheduled posts
+#[poise::command(slash_command, guild_only)]
+async fn timezone(
+    ctx: Context<'_>,
+    #[description = "IANA timezone, e.g. America/Chicago"]
+    #[autocomplete = "autocomplete_timezone"]
+    timezone: String,
+) -> Result<(), Error> {
+    let Ok(tz) = timezone.trim().parse::<Tz>() else {
+        ctx.say(format!("`{}` is not a known timezone.", timezone))
+            .await?;
+        return Ok(());
+    };
+    update_guild(ctx, |guild| {
+        guild.timezone = tz.name().to_string();
+        guild.wotd_configured_at = Some(Utc::now());
+    })
+    .await?;
+
+    ctx.say(format!("Timezone set to {}.", tz.name())).await?;
+    Ok(())
+}
+
+/// Manage the roles given to new members
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("autorole_add", "autorole_remove", "autorole_list"),
+    subcommand_required
+)]
+async fn autorole(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Give a role to new members when they join
+#[poise::command(slash_command, guild_only, rename = "add")]
+async fn autorole_add(
+    ctx: Context<'_>,
+    #[description = "Role to give"] role: Role,
+    #[description = "Who gets the role, everyone by default"] target: Option<AutoRoleTarget>,
+    #[description = "Seconds to wait before giving the role"]
+    #[max = 86400]
+    delay: Option<u64>,
+) -> Result<(), Error> {
+    let rule = AutoRole {
+        guild_id: ctx.guild_id().unwrap(),
+        role_id: role.id,
+        target: target.unwrap_or(AutoRoleTarget::Everyone),
+        delay_secs: delay.unwrap_or(0),
+    };
+    ctx.data().db.set_auto_role(&rule)?;
+
+    ctx.say(format!("New members will get: {}", rule_line(&rule)))
+        .await?;
+    Ok(())
+}
+
+/// Stop giving a role to new members
+#[poise::command(slash_command, guild_only, rename = "remove")]
+async fn autorole_remove(
+    ctx: Context<'_>,
+    #[description = "Role to stop giving"] role: Role,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let reply = if ctx.data().db.remove_auto_role(guild_id, role.id)? {
+        format!("New members will no longer get {}.", role.mention())
+    } else {
+        format!("{} is not given to new members.", role.mention())
+    };
+
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// List the roles given to new members
+#[poise::command(slash_command, guild_only, rename = "list")]
+async fn autorole_list(ctx: Context<'_>) -> Result<(), Error> {
+    let rules = ctx.data().db.auto_roles(ctx.guild_id().unwrap())?;
+    let reply = if rules.is_empty() {
+        "New members don't get any role.".to_string()
+    } else {
+        rules.iter().map(rule_line).collect::<Vec<_>>().join("\n")
+    };
+
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Change how the bot behaves in voice channels
+#[poise::command(slash_command, guild_only)]
+async fn music(
+    ctx: Context<'_>,
+    #[description = "Seconds to stay in voice with nothing playing"]
+    #[min = 30]
+    #[max = 3600]
+    idle_timeout: Option<u64>,
+    #[description = "Seconds to stay in voice after everyone left"]
+    #[max = 3600]
+    empty_grace: Option<u64>,
+    #[description = "Percent of listeners needed to vote-skip a track"]
+    #[min = 1]
+    #[max = 100]
+    vote_skip: Option<u32>,
+    #[description = "Whether to keep playing related tracks when the queue runs out"]
+    autoplay: Option<bool>,
+) -> Result<(), Error> {
+    let guild = update_guild(ctx, |guild| {
+        if let Some(secs) = idle_timeout {
+            guild.idle_timeout_secs = secs;
+        }
+        if let Some(secs) = empty_grace {
+            guild.empty_grace_secs = secs;
+        }
+        if let Some(percent) = vote_skip {
+            guild.vote_skip_percent = percent;
+        }
+        if let Some(autoplay) = autoplay {
+            guild.autoplay = autoplay;
+        }
+        guild.clone()
+    })
+    .await?;
+
+    let rules = ctx.data().db.auto_roles(guild.guild_id)?;
+    let embed = config_embed(&guild, &rules);
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Set the role that can skip and stop music without a vote
+#[poise::command(slash_command, guild_only)]
+async fn dj_role(
+    ctx: Context<'_>,
+    #[description = "DJ role, leave empty to leave it to server managers"] role: Option<Role>,
+) -> Result<(), Error> {
+    let reply = match &role {
+        Some(role) => format!("{} can now skip and stop freely.", role.mention()),
+        None => "Only server managers can skip and stop freely now.".to_string(),
+    };
+    update_guild(ctx, |guild| guild.dj_role_id = role.as_ref().map(|r| r.id)).await?;
+
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+async fn update_guild<R>(ctx: Context<'_>, f: impl FnOnce(&mut Guild) -> R) -> Result<R, Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let name = guild_name(ctx);
+    Ok(ctx.data().guilds.update(guild_id, &name, f).await?)
+}
+
+fn guild_name(ctx: Context<'_>) -> String {
+    ctx.guild().map(|g| g.name.clone()).unwrap_or_default()
+}
+
+fn rule_line(rule: &AutoRole) -> String {
+    let mut line = format!("{} for {}", rule.role_id.mention(), rule.target.as_str());
+    if rule.delay_secs > 0 {
+        line += &format!(" after {}s", rule.delay_secs);
+    }
+    line
+}
+
+fn config_embed(guild: &Guild, rules: &[AutoRole]) -> CreateEmbed {
+    let footer = CreateEmbedFooter::new("rauly.rs");
+    let wotd_channel = match (guild.setup, guild.wotd_channel_id) {
+        (true, Some(channel_id)) => channel_id.mention().to_string(),
+        _ => "Disabled".to_string(),
+    };
+    let auto_roles = if rules.is_empty() {
+        "None".to_string()
+    } else {
+        rules.iter().map(rule_line).collect::<Vec<_>>().join("\n")
+    };
+
+    CreateEmbed::new()
+        .colour(0xeb984e)
+        .title(format!("Configuration | {}", guild.guild))
+        .field("Word of the Day channel", wotd_channel, false)
+        .field("Word of the Day time", &guild.wotd_time, true)
+        .field("Timezone", &guild.timezone, true)
+        .field("Auto roles", auto_roles, false)
+        .field(
+            "Idle timeout",
+            format!("{}s", guild.idle_timeout_secs),
+            true,
+        )
+        .field(
+            "Empty channel grace",
+            format!("{}s", guild.empty_grace_secs),
+            true,
+        )
+        .field("Volume", format!("{}%", guild.volume), true)
+        .field(
+            "DJ role",
+            guild
+                .dj_role_id
+                .map_or("None".to_string(), |id| id.mention().to_string()),
+            true,
+        )
+        .field("Vote skip", format!("{}%", guild.vote_skip_percent), true)
+        .field("Autoplay", if guild.autoplay { "On" } else { "Off" }, true)
+        .footer(footer)
+}
@@ -1,3 +1,4 @@
+pub mod config;
 pub mod guild;
 pub mod math;
 pub mod quotes;
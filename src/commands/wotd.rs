@@ -8,20 +8,29 @@ use serenity::builder::{CreateEmbed, CreateEmbedFooter};
     description_localized("en-US", "Displays the Word of the Day")
 )]
 pub async fn word(ctx: Context<'_>) -> Result<(), Error> {
-    let dt = Utc::now().format("%B %d, %Y");
-    let color = utils::COLORS.choose(&mut rand::thread_rng()).unwrap();
-
     let Ok(res) = word::get_word().await else {
         ctx.say("Had a problem parsing JSON!").await?;
         return Ok(());
     };
 
+    let embed = word_embed(&res, Utc::now().date_naive());
+    let msg = poise::CreateReply::default().embed(embed);
+    ctx.send(msg).await?;
+
+    Ok(())
+}
+
+/// Builds the Word of the Day embed, shared by the command and the daily scheduler.
+pub fn word_embed(res: &word::Response, date: NaiveDate) -> CreateEmbed {
+    let dt = date.format("%B %d, %Y");
+    let color = utils::COLORS.choose(&mut rand::thread_rng()).unwrap();
+
     let example = res.examples[0]
         .text
         .replace(&res.word, &format!("**{}**", res.word));
 
     let footer = CreateEmbedFooter::new("Word of the Day");
-    let embed = CreateEmbed::new()
+    CreateEmbed::new()
         .title(format!("{} | {}", res.word, dt))
         .color(*color)
         .field(
@@ -31,10 +40,6 @@ pub async fn word(ctx: Context<'_>) -> Result<(), Error> {
         )
         .field("Definition", &res.definitions[0].text, false)
         .field("Example", example, false)
-        .field("Note", res.note, false)
-        .footer(footer);
-    let msg = poise::CreateReply::default().embed(embed);
-    ctx.send(msg).await?;
-
-    Ok(())
+        .field("Note", &res.note, false)
+        .footer(footer)
 }
@@ -0,0 +1,84 @@
+use crate::db::Database;
+use crate::models::auto_role::{AutoRole, AutoRoleTarget};
+use anyhow::Result;
+use rusqlite::{params, Row};
+use serenity::model::prelude::*;
+use std::env;
+use tracing::info;
+
+impl Database {
+    pub fn auto_roles(&self, guild_id: GuildId) -> Result<Vec<AutoRole>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT guild_id, role_id, target, delay_secs FROM auto_roles
+             WHERE guild_id = ?1 ORDER BY delay_secs, role_id",
+        )?;
+        let rows = stmt.query_map([guild_id.get()], from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Adds a rule, replacing any existing rule for the same role.
+    pub fn set_auto_role(&self, rule: &AutoRole) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO auto_roles (guild_id, role_id, target, delay_secs)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (guild_id, role_id) DO UPDATE SET
+                target = excluded.target,
+                delay_secs = excluded.delay_secs",
+            params![
+                rule.guild_id.get(),
+                rule.role_id.get(),
+                rule.target.as_str(),
+                rule.delay_secs,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns whether there was a rule to remove.
+    pub fn remove_auto_role(&self, guild_id: GuildId, role_id: RoleId) -> Result<bool> {
+        let removed = self.conn().execute(
+            "DELETE FROM auto_roles WHERE guild_id = ?1 AND role_id = ?2",
+            params![guild_id.get(), role_id.get()],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Turns the old single-server `GUILD_ID`/`ROLE_ID` environment variables
+    /// into a rule, unless that guild already has rules of its own. This only
+    /// happens once, so the rule stays gone if an admin removes it.
+    pub fn import_legacy_auto_role(&self) -> Result<()> {
+        let (Ok(guild_id), Ok(role_id)) = (env::var("GUILD_ID"), env::var("ROLE_ID")) else {
+            return Ok(());
+        };
+        let (Ok(guild_id), Ok(role_id)) = (guild_id.parse::<GuildId>(), role_id.parse::<RoleId>())
+        else {
+            return Ok(());
+        };
+        let first_time = self.conn().execute(
+            "INSERT OR IGNORE INTO legacy_imports (name) VALUES ('auto_role')",
+            [],
+        )? > 0;
+        if !first_time || !self.auto_roles(guild_id)?.is_empty() {
+            return Ok(());
+        }
+
+        info!("Importing auto role from GUILD_ID/ROLE_ID");
+        self.set_auto_role(&AutoRole {
+            guild_id,
+            role_id,
+            target: AutoRoleTarget::Everyone,
+            delay_secs: 0,
+        })
+    }
+}
+
+fn from_row(row: &Row) -> rusqlite::Result<AutoRole> {
+    let target: String = row.get("target")?;
+    Ok(AutoRole {
+        guild_id: GuildId::new(row.get("guild_id")?),
+        role_id: RoleId::new(row.get("role_id")?),
+        target: AutoRoleTarget::parse(&target).unwrap_or(AutoRoleTarget::Everyone),
+        delay_secs: row.get("delay_secs")?,
+    })
+}
@@ -0,0 +1,179 @@
+use crate::db::Database;
+use crate::models::guild::{Guild, CONFIG_PATH};
+use anyhow::Result;
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serenity::model::prelude::*;
+use serenity::prelude::RwLock;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Last posted dates written by the scheduler before the database existed.
+const LEGACY_STATE_PATH: &str = "wotd_state.json";
+
+/// Typed access to the `guilds` table, with every row cached in memory.
+///
+/// Writes go to the database first and hold the cache lock while doing so,
+/// which keeps concurrent updates from commands in order.
+pub struct GuildStore {
+    db: Database,
+    cache: RwLock<HashMap<GuildId, Guild>>,
+}
+
+impl GuildStore {
+    pub fn load(db: Database) -> Result<Self> {
+        let guilds = {
+            let conn = db.conn();
+            import_legacy_config(&conn)?;
+            let mut stmt = conn.prepare("SELECT * FROM guilds")?;
+            let rows = stmt.query_map([], from_row)?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        info!("Loaded configuration for {} guilds", guilds.len());
+
+        let cache = guilds.into_iter().map(|g| (g.guild_id, g)).collect();
+        Ok(Self {
+            db,
+            cache: RwLock::new(cache),
+        })
+    }
+
+    pub async fn get(&self, guild_id: GuildId) -> Option<Guild> {
+        self.cache.read().await.get(&guild_id).cloned()
+    }
+
+    pub async fn all(&self) -> Vec<Guild> {
+        self.cache.read().await.values().cloned().collect()
+    }
+
+    /// Applies `f` to a guild's configuration and saves it, creating the
+    /// record with defaults if the guild has none yet.
+    pub async fn update<R>(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+        f: impl FnOnce(&mut Guild) -> R,
+    ) -> Result<R> {
+        let mut cache = self.cache.write().await;
+        let mut guild = cache
+            .get(&guild_id)
+            .cloned()
+            .unwrap_or_else(|| Guild::new(guild_id, name));
+        let result = f(&mut guild);
+        save(&self.db.conn(), &guild)?;
+        cache.insert(guild_id, guild);
+        Ok(result)
+    }
+
+    /// Records that the Word of the Day went out for `date`, local to the guild.
+    pub async fn set_wotd_posted(&self, guild_id: GuildId, date: NaiveDate) -> Result<()> {
+        let mut cache = self.cache.write().await;
+        self.db.conn().execute(
+            "UPDATE guilds SET wotd_last_posted = ?1 WHERE guild_id = ?2",
+            params![date.to_string(), guild_id.get()],
+        )?;
+        if let Some(guild) = cache.get_mut(&guild_id) {
+            guild.wotd_last_posted = Some(date);
+        }
+        Ok(())
+    }
+}
+
+fn from_row(row: &Row) -> rusqlite::Result<Guild> {
+    let last_posted: Option<String> = row.get("wotd_last_posted")?;
+    let configured_at: Option<String> = row.get("wotd_configured_at")?;
+    Ok(Guild {
+        setup: row.get("setup")?,
+        guild: row.get("name")?,
+        guild_id: GuildId::new(row.get("guild_id")?),
+        wotd_channel: row.get("wotd_channel")?,
+        wotd_channel_id: row
+            .get::<_, Option<u64>>("wotd_channel_id")?
+            .map(ChannelId::new),
+        timezone: row.get("timezone")?,
+        wotd_time: row.get("wotd_time")?,
+        idle_timeout_secs: row.get("idle_timeout_secs")?,
+        empty_grace_secs: row.get("empty_grace_secs")?,
+        volume: row.get("volume")?,
+        dj_role_id: row.get::<_, Option<u64>>("dj_role_id")?.map(RoleId::new),
+        vote_skip_percent: row.get("vote_skip_percent")?,
+        autoplay: row.get("autoplay")?,
+        wotd_last_posted: last_posted.and_then(|d| d.parse().ok()),
+        wotd_configured_at: configured_at.and_then(|t| t.parse().ok()),
+    })
+}
+
+fn save(conn: &Connection, guild: &Guild) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO guilds
+            (guild_id, name, setup, wotd_channel, wotd_channel_id, timezone, wotd_time,
+             idle_timeout_secs, empty_grace_secs, volume, dj_role_id, vote_skip_percent,
+             autoplay, wotd_last_posted, wotd_configured_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+         ON CONFLICT (guild_id) DO UPDATE SET
+            name = excluded.name,
+            setup = excluded.setup,
+            wotd_channel = excluded.wotd_channel,
+            wotd_channel_id = excluded.wotd_channel_id,
+            timezone = excluded.timezone,
+            wotd_time = excluded.wotd_time,
+            idle_timeout_secs = excluded.idle_timeout_secs,
+            empty_grace_secs = excluded.empty_grace_secs,
+            volume = excluded.volume,
+            dj_role_id = excluded.dj_role_id,
+            vote_skip_percent = excluded.vote_skip_percent,
+            autoplay = excluded.autoplay,
+            wotd_last_posted = excluded.wotd_last_posted,
+            wotd_configured_at = excluded.wotd_configured_at",
+        params![
+            guild.guild_id.get(),
+            guild.guild,
+            guild.setup,
+            guild.wotd_channel,
+            guild.wotd_channel_id.map(|id| id.get()),
+            guild.timezone,
+            guild.wotd_time,
+            guild.idle_timeout_secs,
+            guild.empty_grace_secs,
+            guild.volume,
+            guild.dj_role_id.map(|id| id.get()),
+            guild.vote_skip_percent,
+            guild.autoplay,
+            guild.wotd_last_posted.map(|d| d.to_string()),
+            guild.wotd_configured_at.map(|t| t.to_rfc3339()),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Seeds an empty `guilds` table from `config.json` and the scheduler's old
+/// state file, so existing deployments keep their settings.
+fn import_legacy_config(conn: &Connection) -> Result<()> {
+    let existing: Option<u64> = conn
+        .query_row("SELECT guild_id FROM guilds LIMIT 1", [], |row| row.get(0))
+        .optional()?;
+    if existing.is_some() || !Path::new(CONFIG_PATH).exists() {
+        return Ok(());
+    }
+
+    let posted: HashMap<GuildId, String> = fs::read_to_string(LEGACY_STATE_PATH)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+
+    let guilds = match Guild::load_all(CONFIG_PATH) {
+        Ok(guilds) => guilds,
+        Err(e) => {
+            warn!("Not importing {}: {:#}", CONFIG_PATH, e);
+            return Ok(());
+        }
+    };
+    for mut guild in guilds {
+        guild.wotd_last_posted = posted.get(&guild.guild_id).and_then(|d| d.parse().ok());
+        save(conn, &guild)?;
+    }
+    info!("Imported guild configuration from {}", CONFIG_PATH);
+    Ok(())
+}
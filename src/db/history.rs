@@ -0,0 +1,61 @@
+use crate::db::queues::track_from_row;
+use crate::db::Database;
+use crate::models::queue::{PlayedTrack, SavedTrack};
+use anyhow::Result;
+use chrono::Utc;
+use rusqlite::params;
+use serenity::model::prelude::*;
+
+/// How many played tracks are kept per guild, older ones are dropped.
+const HISTORY_LIMIT: usize = 200;
+
+impl Database {
+    /// Adds a track that just finished playing to the guild's history.
+    pub fn record_play(&self, guild_id: GuildId, track: &SavedTrack) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO play_history
+                (guild_id, url, title, artist, duration_ms, thumbnail, requester_id, source,
+                 enqueued_at, played_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                guild_id.get(),
+                track.url,
+                track.title,
+                track.artist,
+                track.duration.map(|d| d.as_millis() as u64),
+                track.thumbnail,
+                track.requester.map(|id| id.get()),
+                track.source,
+                track.enqueued_at.to_rfc3339(),
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        tx.execute(
+            "DELETE FROM play_history WHERE guild_id = ?1 AND id NOT IN
+                (SELECT id FROM play_history WHERE guild_id = ?1 ORDER BY id DESC LIMIT ?2)",
+            params![guild_id.get(), HISTORY_LIMIT],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The guild's played tracks, most recent first.
+    pub fn history(&self, guild_id: GuildId) -> Result<Vec<PlayedTrack>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT url, title, artist, duration_ms, thumbnail, requester_id, source,
+                    enqueued_at, played_at
+             FROM play_history WHERE guild_id = ?1 ORDER BY id DESC",
+        )?;
+        let rows = stmt.query_map([guild_id.get()], |row| {
+            let played_at: String = row.get("played_at")?;
+            Ok(PlayedTrack {
+                track: track_from_row(row)?,
+                played_at: played_at.parse().unwrap_or_default(),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
@@ -0,0 +1,163 @@
+pub mod auto_roles;
+pub mod guilds;
+pub mod history;
+pub mod playlists;
+pub mod queues;
+pub mod radio;
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use std::env;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::info;
+
+const DEFAULT_DATABASE_PATH: &str = "raulyrs.db";
+
+/// Schema changes, applied in order. `PRAGMA user_version` records how many
+/// have been applied, so only ever append to this list.
+const MIGRATIONS: &[&str] = &[
+    // 1: guild configuration
+    "CREATE TABLE guilds (
+        guild_id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        setup INTEGER NOT NULL DEFAULT 0,
+        wotd_channel TEXT NOT NULL DEFAULT '',
+        wotd_channel_id INTEGER,
+        timezone TEXT NOT NULL DEFAULT 'UTC',
+        wotd_time TEXT NOT NULL DEFAULT '09:00',
+        wotd_last_posted TEXT
+    );",
+    // 2: per-guild settings managed through /config
+    "ALTER TABLE guilds ADD COLUMN auto_role_id INTEGER;
+    ALTER TABLE guilds ADD COLUMN idle_timeout_secs INTEGER NOT NULL DEFAULT 420;
+    ALTER TABLE guilds ADD COLUMN empty_grace_secs INTEGER NOT NULL DEFAULT 60;",
+    // 3: auto role rules, replacing the single auto_role_id
+    "CREATE TABLE auto_roles (
+        guild_id INTEGER NOT NULL,
+        role_id INTEGER NOT NULL,
+        target TEXT NOT NULL,
+        delay_secs INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (guild_id, role_id)
+    );
+    INSERT INTO auto_roles (guild_id, role_id, target)
+        SELECT guild_id, auto_role_id, 'everyone' FROM guilds WHERE auto_role_id IS NOT NULL;
+    ALTER TABLE guilds DROP COLUMN auto_role_id;",
+    // 4: default playback volume
+    "ALTER TABLE guilds ADD COLUMN volume INTEGER NOT NULL DEFAULT 100;",
+    // 5: DJ role and vote-skip threshold
+    "ALTER TABLE guilds ADD COLUMN dj_role_id INTEGER;
+    ALTER TABLE guilds ADD COLUMN vote_skip_percent INTEGER NOT NULL DEFAULT 50;",
+    // 6: queues saved across restarts
+    "CREATE TABLE queue_snapshots (
+        guild_id INTEGER PRIMARY KEY,
+        channel_id INTEGER,
+        loop_mode TEXT NOT NULL DEFAULT 'off',
+        fair_queue INTEGER NOT NULL DEFAULT 0,
+        position_ms INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE queue_tracks (
+        guild_id INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        url TEXT NOT NULL,
+        title TEXT NOT NULL,
+        artist TEXT,
+        duration_ms INTEGER,
+        thumbnail TEXT,
+        requester_id INTEGER,
+        source TEXT NOT NULL,
+        enqueued_at TEXT NOT NULL,
+        PRIMARY KEY (guild_id, position)
+    );",
+    // 7: saved playlists, personal when guild_id is NULL
+    "CREATE TABLE playlists (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        owner_id INTEGER NOT NULL,
+        guild_id INTEGER
+    );
+    CREATE UNIQUE INDEX personal_playlist_names ON playlists (owner_id, name)
+        WHERE guild_id IS NULL;
+    CREATE UNIQUE INDEX guild_playlist_names ON playlists (guild_id, name)
+        WHERE guild_id IS NOT NULL;
+    CREATE TABLE playlist_tracks (
+        id INTEGER PRIMARY KEY,
+        playlist_id INTEGER NOT NULL,
+        url TEXT NOT NULL,
+        title TEXT NOT NULL,
+        artist TEXT,
+        duration_ms INTEGER,
+        thumbnail TEXT,
+        requester_id INTEGER,
+        source TEXT NOT NULL,
+        added_at TEXT NOT NULL
+    );
+    CREATE INDEX playlist_tracks_playlist ON playlist_tracks (playlist_id);",
+    // 8: tracks played in each guild
+    "CREATE TABLE play_history (
+        id INTEGER PRIMARY KEY,
+        guild_id INTEGER NOT NULL,
+        url TEXT NOT NULL,
+        title TEXT NOT NULL,
+        artist TEXT,
+        duration_ms INTEGER,
+        thumbnail TEXT,
+        requester_id INTEGER,
+        source TEXT NOT NULL,
+        enqueued_at TEXT NOT NULL,
+        played_at TEXT NOT NULL
+    );
+    CREATE INDEX play_history_guild ON play_history (guild_id, id);",
+    // 9: radio stations saved for a guild
+    "CREATE TABLE radio_stations (
+        guild_id INTEGER NOT NULL,
+        name TEXT NOT NULL COLLATE NOCASE,
+        url TEXT NOT NULL,
+        added_by INTEGER NOT NULL,
+        PRIMARY KEY (guild_id, name)
+    );",
+    // 10: autoplay of related tracks
+    "ALTER TABLE guilds ADD COLUMN autoplay INTEGER NOT NULL DEFAULT 0;",
+    // 11: one-time imports of legacy settings that have already run
+    "CREATE TABLE legacy_imports (
+        name TEXT PRIMARY KEY
+    );",
+    // 12: when the Word of the Day schedule last changed
+    "ALTER TABLE guilds ADD COLUMN wotd_configured_at TEXT;",
+];
+
+/// Handle to the bot's SQLite database, cheap to clone and share between tasks.
+#[derive(Clone)]
+pub struct Database {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Database {
+    /// Opens the database at `DATABASE_PATH` (or `raulyrs.db`) and migrates it.
+    pub fn open() -> Result<Self> {
+        let path = env::var("DATABASE_PATH").unwrap_or_else(|_| DEFAULT_DATABASE_PATH.to_string());
+        let conn = Connection::open(&path).with_context(|| format!("opening {}", path))?;
+        info!("Opened database {}", path);
+        Self::from_connection(conn)
+    }
+
+    fn from_connection(mut conn: Connection) -> Result<Self> {
+        let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            info!("Applying database migration {}", i + 1);
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration)
+                .with_context(|| format!("migration {}", i + 1))?;
+            tx.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+        }
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Locks the connection. Statements are short, so this is held only briefly
+    /// and never across an `.await`.
+    pub fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().expect("database mutex poisoned")
+    }
+}
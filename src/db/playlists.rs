@@ -0,0 +1,147 @@
+use crate::db::queues::track_from_row;
+use crate::db::Database;
+use crate::models::playlist::Playlist;
+use crate::models::queue::SavedTrack;
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension, Row};
+use serenity::model::prelude::*;
+
+const PLAYLIST_COLUMNS: &str = "id, name, owner_id, guild_id,
+    (SELECT COUNT(*) FROM playlist_tracks WHERE playlist_id = playlists.id) AS track_count";
+
+impl Database {
+    /// Creates an empty playlist. Returns `None` if the name is already taken,
+    /// by the user for personal playlists or in the guild for shared ones.
+    pub fn create_playlist(
+        &self,
+        owner_id: UserId,
+        guild_id: Option<GuildId>,
+        name: &str,
+    ) -> Result<Option<Playlist>> {
+        let conn = self.conn();
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO playlists (name, owner_id, guild_id) VALUES (?1, ?2, ?3)",
+            params![name, owner_id.get(), guild_id.map(|id| id.get())],
+        )?;
+        if inserted == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Playlist {
+            id: conn.last_insert_rowid(),
+            name: name.to_string(),
+            owner_id,
+            guild_id,
+            track_count: 0,
+        }))
+    }
+
+    /// Every playlist a member can use in a guild: their own, then the guild's.
+    pub fn playlists(&self, user_id: UserId, guild_id: GuildId) -> Result<Vec<Playlist>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {PLAYLIST_COLUMNS} FROM playlists
+             WHERE (owner_id = ?1 AND guild_id IS NULL) OR guild_id = ?2
+             ORDER BY guild_id IS NOT NULL, name"
+        ))?;
+        let rows = stmt.query_map(params![user_id.get(), guild_id.get()], from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Looks a playlist up by name, preferring the member's own over the guild's.
+    pub fn find_playlist(
+        &self,
+        user_id: UserId,
+        guild_id: GuildId,
+        name: &str,
+    ) -> Result<Option<Playlist>> {
+        Ok(self
+            .conn()
+            .query_row(
+                &format!(
+                    "SELECT {PLAYLIST_COLUMNS} FROM playlists
+                     WHERE name = ?3 AND ((owner_id = ?1 AND guild_id IS NULL) OR guild_id = ?2)
+                     ORDER BY guild_id IS NOT NULL LIMIT 1"
+                ),
+                params![user_id.get(), guild_id.get(), name],
+                from_row,
+            )
+            .optional()?)
+    }
+
+    pub fn playlist_tracks(&self, playlist_id: i64) -> Result<Vec<SavedTrack>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT url, title, artist, duration_ms, thumbnail, requester_id, source,
+                    added_at AS enqueued_at
+             FROM playlist_tracks WHERE playlist_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map([playlist_id], track_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn add_playlist_track(&self, playlist_id: i64, track: &SavedTrack) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO playlist_tracks
+                (playlist_id, url, title, artist, duration_ms, thumbnail, requester_id, source,
+                 added_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                playlist_id,
+                track.url,
+                track.title,
+                track.artist,
+                track.duration.map(|d| d.as_millis() as u64),
+                track.thumbnail,
+                track.requester.map(|id| id.get()),
+                track.source,
+                track.enqueued_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Removes the track at a 1-based position, returning it if there was one.
+    pub fn remove_playlist_track(
+        &self,
+        playlist_id: i64,
+        position: usize,
+    ) -> Result<Option<SavedTrack>> {
+        let conn = self.conn();
+        let track = conn
+            .query_row(
+                "SELECT id, url, title, artist, duration_ms, thumbnail, requester_id, source,
+                        added_at AS enqueued_at
+                 FROM playlist_tracks WHERE playlist_id = ?1 ORDER BY id LIMIT 1 OFFSET ?2",
+                params![playlist_id, position.saturating_sub(1)],
+                |row| Ok((row.get::<_, i64>("id")?, track_from_row(row)?)),
+            )
+            .optional()?;
+        let Some((id, track)) = track else {
+            return Ok(None);
+        };
+        conn.execute("DELETE FROM playlist_tracks WHERE id = ?1", [id])?;
+        Ok(Some(track))
+    }
+
+    pub fn delete_playlist(&self, playlist_id: i64) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM playlist_tracks WHERE playlist_id = ?1",
+            [playlist_id],
+        )?;
+        tx.execute("DELETE FROM playlists WHERE id = ?1", [playlist_id])?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn from_row(row: &Row) -> rusqlite::Result<Playlist> {
+    Ok(Playlist {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        owner_id: UserId::new(row.get("owner_id")?),
+        guild_id: row.get::<_, Option<u64>>("guild_id")?.map(GuildId::new),
+        track_count: row.get("track_count")?,
+    })
+}
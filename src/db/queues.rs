@@ -0,0 +1,115 @@
+use crate::db::Database;
+use crate::models::queue::{LoopMode, QueueSnapshot, SavedTrack};
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension, Row};
+use serenity::model::prelude::*;
+use std::time::Duration;
+
+impl Database {
+    /// Replaces the saved queue of a guild. An empty queue just clears it.
+    pub fn save_queue(&self, snapshot: &QueueSnapshot) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let guild_id = snapshot.guild_id.get();
+        tx.execute("DELETE FROM queue_tracks WHERE guild_id = ?1", [guild_id])?;
+        tx.execute(
+            "DELETE FROM queue_snapshots WHERE guild_id = ?1",
+            [guild_id],
+        )?;
+        if !snapshot.tracks.is_empty() {
+            tx.execute(
+                "INSERT INTO queue_snapshots (guild_id, channel_id, loop_mode, fair_queue, position_ms)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    guild_id,
+                    snapshot.channel_id.map(|id| id.get()),
+                    snapshot.loop_mode.as_str(),
+                    snapshot.fair_queue,
+                    snapshot.position.as_millis() as u64,
+                ],
+            )?;
+            let mut stmt = tx.prepare(
+                "INSERT INTO queue_tracks
+                    (guild_id, position, url, title, artist, duration_ms, thumbnail,
+                     requester_id, source, enqueued_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            for (position, track) in snapshot.tracks.iter().enumerate() {
+                stmt.execute(params![
+                    guild_id,
+                    position,
+                    track.url,
+                    track.title,
+                    track.artist,
+                    track.duration.map(|d| d.as_millis() as u64),
+                    track.thumbnail,
+                    track.requester.map(|id| id.get()),
+                    track.source,
+                    track.enqueued_at.to_rfc3339(),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn delete_queue(&self, guild_id: GuildId) -> Result<()> {
+        self.save_queue(&QueueSnapshot {
+            guild_id,
+            channel_id: None,
+            loop_mode: LoopMode::Off,
+            fair_queue: false,
+            position: Duration::ZERO,
+            tracks: Vec::new(),
+        })
+    }
+
+    pub fn load_queue(&self, guild_id: GuildId) -> Result<Option<QueueSnapshot>> {
+        let conn = self.conn();
+        let snapshot = conn
+            .query_row(
+                "SELECT guild_id, channel_id, loop_mode, fair_queue, position_ms
+                 FROM queue_snapshots WHERE guild_id = ?1",
+                [guild_id.get()],
+                |row| {
+                    let loop_mode: String = row.get("loop_mode")?;
+                    Ok(QueueSnapshot {
+                        guild_id,
+                        channel_id: row.get::<_, Option<u64>>("channel_id")?.map(ChannelId::new),
+                        loop_mode: LoopMode::parse(&loop_mode).unwrap_or_default(),
+                        fair_queue: row.get("fair_queue")?,
+                        position: Duration::from_millis(row.get("position_ms")?),
+                        tracks: Vec::new(),
+                    })
+                },
+            )
+            .optional()?;
+        let Some(mut snapshot) = snapshot else {
+            return Ok(None);
+        };
+
+        let mut stmt = conn.prepare(
+            "SELECT url, title, artist, duration_ms, thumbnail, requester_id, source, enqueued_at
+             FROM queue_tracks WHERE guild_id = ?1 ORDER BY position",
+        )?;
+        let rows = stmt.query_map([guild_id.get()], track_from_row)?;
+        snapshot.tracks = rows.collect::<rusqlite::Result<_>>()?;
+        Ok(Some(snapshot))
+    }
+}
+
+pub(super) fn track_from_row(row: &Row) -> rusqlite::Result<SavedTrack> {
+    let enqueued_at: String = row.get("enqueued_at")?;
+    Ok(SavedTrack {
+        url: row.get("url")?,
+        title: row.get("title")?,
+        artist: row.get("artist")?,
+        duration: row
+            .get::<_, Option<u64>>("duration_ms")?
+            .map(Duration::from_millis),
+        thumbnail: row.get("thumbnail")?,
+        requester: row.get::<_, Option<u64>>("requester_id")?.map(UserId::new),
+        source: row.get("source")?,
+        enqueued_at: enqueued_at.parse().unwrap_or_default(),
+    })
+}
@@ -0,0 +1,63 @@
+use crate::db::Database;
+use crate::models::radio::Station;
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension, Row};
+use serenity::model::prelude::*;
+
+impl Database {
+    /// Saves a station, returning `false` if the guild already has one with
+    /// that name.
+    pub fn save_station(&self, station: &Station) -> Result<bool> {
+        let inserted = self.conn().execute(
+            "INSERT OR IGNORE INTO radio_stations (guild_id, name, url, added_by)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                station.guild_id.get(),
+                station.name,
+                station.url,
+                station.added_by.get(),
+            ],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    pub fn stations(&self, guild_id: GuildId) -> Result<Vec<Station>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT guild_id, name, url, added_by FROM radio_stations
+             WHERE guild_id = ?1 ORDER BY name",
+        )?;
+        let rows = stmt.query_map([guild_id.get()], from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Looks a station up by name, ignoring case.
+    pub fn find_station(&self, guild_id: GuildId, name: &str) -> Result<Option<Station>> {
+        Ok(self
+            .conn()
+            .query_row(
+                "SELECT guild_id, name, url, added_by FROM radio_stations
+                 WHERE guild_id = ?1 AND name = ?2",
+                params![guild_id.get(), name],
+                from_row,
+            )
+            .optional()?)
+    }
+
+    pub fn delete_station(&self, guild_id: GuildId, name: &str) -> Result<()> {
+        self.conn().execute(
+            "DELETE FROM radio_stations WHERE guild_id = ?1 AND name = ?2",
+            params![guild_id.get(), name],
+        )?;
+        Ok(())
+    }
+}
+
+fn from_row(row: &Row) -> rusqlite::Result<Station> {
+    Ok(Station {
+        guild_id: GuildId::new(row.get("guild_id")?),
+        name: row.get("name")?,
+        url: row.get("url")?,
+        added_by: UserId::new(row.get("added_by")?),
+    })
+}
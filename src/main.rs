@@ -1,19 +1,24 @@
+mod auto_role;
 mod commands;
+mod db;
 mod models;
+mod scheduler;
 mod utils;
 mod voice;
 
+use crate::db::{guilds::GuildStore, Database};
+use crate::models::guild::DEFAULT_EMPTY_GRACE_SECS;
 use crate::voice::cmds::VoiceHttpKey;
 use reqwest::Client as HttpClient;
 use serenity::{
-    all::{GuildId, RoleId},
     async_trait,
     gateway::ShardManager,
-    model::{event::ResumedEvent, gateway::Ready, guild::Member},
+    model::{event::ResumedEvent, gateway::Ready, guild::Member, voice::VoiceState},
     prelude::*,
 };
-use songbird::SerenityInit;
-use std::{env, sync::Arc};
+use songbird::{SerenityInit, Songbird};
+use std::{env, sync::Arc, time::Duration};
+use tokio::signal::unix::{signal, SignalKind};
 use tracing::{error, info};
 
 pub struct ShardManagerContainer;
@@ -22,11 +27,18 @@ impl TypeMapKey for ShardManagerContainer {
     type Value = Arc<ShardManager>;
 }
 
-struct Data {} // User data, which is stored and accessible in all command invocations
+// User data, which is stored and accessible in all command invocations
+struct Data {
+    pub db: Database,
+    pub guilds: Arc<GuildStore>,
+}
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, Data, Error>;
 
-struct Handler;
+struct Handler {
+    db: Database,
+    guilds: Arc<GuildStore>,
+}
 
 #[async_trait]
 impl EventHandler for Handler {
@@ -38,34 +50,28 @@ impl EventHandler for Handler {
         info!("Resumed");
     }
 
-    async fn guild_member_addition(&self, ctx: serenity::client::Context, mut _member: Member) {
-        let guild_id = env::var("GUILD_ID")
-            .expect("Guild ID")
-            .parse::<GuildId>()
-            .expect("GUILD_ID as u64");
-
-        let role_id = env::var("ROLE_ID")
-            .expect("Role ID")
-            .parse::<RoleId>()
-            .expect("ROLE_ID as u64");
-
-        let user_id = _member.user.id;
-
-        if _member.guild_id != guild_id {
-            return;
+    async fn voice_state_update(
+        &self,
+        ctx: serenity::client::Context,
+        _: Option<VoiceState>,
+        new: VoiceState,
+    ) {
+        if let Some(guild_id) = new.guild_id {
+            let grace = match self.guilds.get(guild_id).await {
+                Some(guild) => guild.empty_grace_secs,
+                None => DEFAULT_EMPTY_GRACE_SECS,
+            };
+            voice::disconnect_handler::voice_state_update(
+                &ctx,
+                guild_id,
+                Duration::from_secs(grace),
+            )
+            .await;
         }
+    }
 
-        match ctx
-            .http
-            .add_member_role(guild_id, user_id, role_id, Some("Default Role"))
-            .await
-        {
-            Ok(_) => info!("added role to new member"),
-            Err(e) => {
-                error!("error adding role to member: {}", e);
-                return;
-            }
-        };
+    async fn guild_member_addition(&self, ctx: serenity::client::Context, member: Member) {
+        auto_role::assign(&ctx, &self.db, &member).await;
     }
 }
 
@@ -83,6 +89,15 @@ async fn main() {
 
     let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
 
+    let db = Database::open().expect("Err opening database");
+    db.import_legacy_auto_role()
+        .expect("Err importing GUILD_ID/ROLE_ID auto role");
+    let guilds = Arc::new(GuildStore::load(db.clone()).expect("Err loading guild configuration"));
+    let data = Data {
+        db: db.clone(),
+        guilds: guilds.clone(),
+    };
+
     // Create the framework
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
@@ -91,7 +106,10 @@ async fn main() {
                 commands::math::multiply(),
                 commands::wotd::word(),
                 commands::guild::get_guild(),
+                commands::config::config(),
                 voice::cmds::voice(),
+                voice::saved_playlists::playlist(),
+                voice::radio::radio(),
             ],
             prefix_options: poise::PrefixFrameworkOptions {
                 prefix: None,
@@ -102,7 +120,7 @@ async fn main() {
         .setup(|ctx, _, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data {})
+                Ok(data)
             })
         })
         .build();
@@ -110,10 +128,14 @@ async fn main() {
     let intents = GatewayIntents::non_privileged()
         | GatewayIntents::MESSAGE_CONTENT
         | GatewayIntents::GUILD_MEMBERS;
+    let songbird = Songbird::serenity();
     let mut client = Client::builder(&token, intents)
         .framework(framework)
-        .register_songbird()
-        .event_handler(Handler)
+        .register_songbird_with(songbird.clone())
+        .event_handler(Handler {
+            db: db.clone(),
+            guilds: guilds.clone(),
+        })
         .type_map_insert::<VoiceHttpKey>(HttpClient::new())
         .await
         .expect("Err creating client");
@@ -123,12 +145,19 @@ async fn main() {
         data.insert::<ShardManagerContainer>(client.shard_manager.clone());
     }
 
+    tokio::spawn(scheduler::run(client.http.clone(), guilds));
+
     let shard_manager = client.shard_manager.clone();
 
     tokio::spawn(async move {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("Could not register ctrl+c handler");
+        // systemd stops the bot with SIGTERM, Ctrl+C sends SIGINT.
+        let mut terminate =
+            signal(SignalKind::terminate()).expect("Could not register SIGTERM handler");
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result.expect("Could not register ctrl+c handler"),
+            _ = terminate.recv() => {}
+        }
+        voice::persist::save_all(&db, &songbird).await;
         shard_manager.shutdown_all().await;
     });
 
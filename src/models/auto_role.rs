@@ -0,0 +1,49 @@
+use serenity::model::prelude::*;
+
+/// Which kind of new member an auto role rule applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum AutoRoleTarget {
+    #[name = "Humans"]
+    Humans,
+    #[name = "Bots"]
+    Bots,
+    #[name = "Everyone"]
+    Everyone,
+}
+
+impl AutoRoleTarget {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AutoRoleTarget::Humans => "humans",
+            AutoRoleTarget::Bots => "bots",
+            AutoRoleTarget::Everyone => "everyone",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "humans" => Some(AutoRoleTarget::Humans),
+            "bots" => Some(AutoRoleTarget::Bots),
+            "everyone" => Some(AutoRoleTarget::Everyone),
+            _ => None,
+        }
+    }
+
+    pub fn matches(self, is_bot: bool) -> bool {
+        match self {
+            AutoRoleTarget::Humans => !is_bot,
+            AutoRoleTarget::Bots => is_bot,
+            AutoRoleTarget::Everyone => true,
+        }
+    }
+}
+
+/// A role handed out to members when they join a guild.
+#[derive(Debug, Clone)]
+pub struct AutoRole {
+    pub guild_id: GuildId,
+    pub role_id: RoleId,
+    pub target: AutoRoleTarget,
+    /// Seconds to wait after the member joins before giving the role.
+    pub delay_secs: u64,
+}
@@ -1,38 +1,106 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::prelude::*;
 use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use serenity::http::Http;
 use serenity::model::prelude::*;
+use std::fs::File;
+use std::io::Read;
 use tracing::info;
 
+/// Legacy configuration file, imported into the database on first start.
+pub const CONFIG_PATH: &str = "config.json";
+pub const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 420;
+pub const DEFAULT_EMPTY_GRACE_SECS: u64 = 60;
+pub const DEFAULT_VOLUME: u32 = 100;
+pub const DEFAULT_VOTE_SKIP_PERCENT: u32 = 50;
+
 pub type Guilds = Vec<Guild>;
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Guild {
-    setup: bool,
-    guild: String,
-    guild_id: GuildId,
-    wotd_channel: String,
-    wotd_channel_id: ChannelId,
-    timezone: String,
-    wotd_time: String,
+    pub setup: bool,
+    pub guild: String,
+    pub guild_id: GuildId,
+    pub wotd_channel: String,
+    pub wotd_channel_id: Option<ChannelId>,
+    pub timezone: String,
+    pub wotd_time: String,
+    /// How long the bot stays in voice with nothing playing.
+    #[serde(default = "default_idle_timeout")]
+    pub idle_timeout_secs: u64,
+    /// How long the bot waits in a voice channel without listeners.
+    #[serde(default = "default_empty_grace")]
+    pub empty_grace_secs: u64,
+    /// Volume in percent applied to every new track.
+    #[serde(default = "default_volume")]
+    pub volume: u32,
+    /// Members with this role can skip and stop without a vote.
+    #[serde(default)]
+    pub dj_role_id: Option<RoleId>,
+    /// Share of listeners that has to vote to skip a track.
+    #[serde(default = "default_vote_skip_percent")]
+    pub vote_skip_percent: u32,
+    /// Whether related tracks keep playing once the queue runs out.
+    #[serde(default)]
+    pub autoplay: bool,
+    /// Local date of the last Word of the Day posted by the scheduler.
+    #[serde(skip)]
+    pub wotd_last_posted: Option<NaiveDate>,
+    /// When the channel, time or timezone of the Word of the Day last
+    /// changed. Posts scheduled before then aren't caught up on.
+    #[serde(skip)]
+    pub wotd_configured_at: Option<DateTime<Utc>>,
 }
 
 impl Guild {
-    // pub fn from_config(config: &str) -> Result<Guild>{
-    //     let mut file = File::open(config)?;
-    //     let mut content = String::new();
-    //     file.read_to_string(&mut content)?;
-    //     Ok(serde_json::from_str(&content)?)
-    // }
+    pub fn new(guild_id: GuildId, name: impl Into<String>) -> Self {
+        Self {
+            setup: false,
+            guild: name.into(),
+            guild_id,
+            wotd_channel: String::new(),
+            wotd_channel_id: None,
+            timezone: "UTC".to_string(),
+            wotd_time: "09:00".to_string(),
+            idle_timeout_secs: DEFAULT_IDLE_TIMEOUT_SECS,
+            empty_grace_secs: DEFAULT_EMPTY_GRACE_SECS,
+            volume: DEFAULT_VOLUME,
+            dj_role_id: None,
+            vote_skip_percent: DEFAULT_VOTE_SKIP_PERCENT,
+            autoplay: false,
+            wotd_last_posted: None,
+            wotd_configured_at: None,
+        }
+    }
+
+    pub fn load_all(config: &str) -> Result<Guilds> {
+        let mut file = File::open(config).with_context(|| format!("opening {}", config))?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn timezone(&self) -> Result<Tz> {
+        self.timezone
+            .parse::<Tz>()
+            .map_err(|e| anyhow::anyhow!("invalid timezone {:?}: {}", self.timezone, e))
+    }
+
+    pub fn wotd_time(&self) -> Result<NaiveTime> {
+        NaiveTime::parse_from_str(&self.wotd_time, "%H:%M")
+            .with_context(|| format!("invalid wotd_time {:?}", self.wotd_time))
+    }
+
     pub async fn test_update(&self, http: &Http) -> Result<Message> {
         info!("Sending update");
-        let tz = self.timezone.parse::<Tz>().unwrap();
+        let channel_id = self
+            .wotd_channel_id
+            .context("no Word of the Day channel configured")?;
+        let tz = self.timezone()?;
         info!("{}", self.wotd_time);
-        let wotime = NaiveTime::parse_from_str(&self.wotd_time, "%H:%M").unwrap();
+        let wotime = self.wotd_time()?;
         let time = Utc::now().with_timezone(&tz);
-        let msg = self
-            .wotd_channel_id
+        let msg = channel_id
             .say(
                 &http,
                 format!("Testing Now: {}\n Word of the Day Time: {}", time, wotime),
@@ -41,3 +109,19 @@ impl Guild {
         Ok(msg)
     }
 }
+
+fn default_idle_timeout() -> u64 {
+    DEFAULT_IDLE_TIMEOUT_SECS
+}
+
+fn default_empty_grace() -> u64 {
+    DEFAULT_EMPTY_GRACE_SECS
+}
+
+fn default_volume() -> u32 {
+    DEFAULT_VOLUME
+}
+
+fn default_vote_skip_percent() -> u32 {
+    DEFAULT_VOTE_SKIP_PERCENT
+}
@@ -1,2 +1,6 @@
+pub mod auto_role;
 pub mod guild;
+pub mod playlist;
+pub mod queue;
+pub mod radio;
 pub mod word;
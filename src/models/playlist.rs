@@ -0,0 +1,31 @@
+use serenity::model::prelude::*;
+
+/// Who a saved playlist belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum PlaylistScope {
+    #[name = "Personal"]
+    Personal,
+    #[name = "Server"]
+    Guild,
+}
+
+/// A named list of tracks kept for later, either one member's own or shared
+/// with everyone in a guild.
+#[derive(Debug, Clone)]
+pub struct Playlist {
+    pub id: i64,
+    pub name: String,
+    pub owner_id: UserId,
+    /// Set for playlists shared with a guild, `None` for personal ones.
+    pub guild_id: Option<GuildId>,
+    pub track_count: usize,
+}
+
+impl Playlist {
+    pub fn scope(&self) -> PlaylistScope {
+        match self.guild_id {
+            Some(_) => PlaylistScope::Guild,
+            None => PlaylistScope::Personal,
+        }
+    }
+}
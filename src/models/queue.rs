@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use serenity::model::prelude::*;
+use std::time::Duration;
+
+/// What to repeat once a track ends.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum LoopMode {
+    #[default]
+    #[name = "Off"]
+    Off,
+    #[name = "Track"]
+    Track,
+    #[name = "Queue"]
+    Queue,
+}
+
+impl LoopMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LoopMode::Off => "off",
+            LoopMode::Track => "track",
+            LoopMode::Queue => "queue",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "off" => Some(LoopMode::Off),
+            "track" => Some(LoopMode::Track),
+            "queue" => Some(LoopMode::Queue),
+            _ => None,
+        }
+    }
+}
+
+/// A track as stored in the database, enough to queue it again later.
+#[derive(Debug, Clone)]
+pub struct SavedTrack {
+    pub url: String,
+    pub title: String,
+    pub artist: Option<String>,
+    pub duration: Option<Duration>,
+    pub thumbnail: Option<String>,
+    pub requester: Option<UserId>,
+    pub source: String,
+    pub enqueued_at: DateTime<Utc>,
+}
+
+/// A track that finished playing in a guild.
+#[derive(Debug, Clone)]
+pub struct PlayedTrack {
+    pub track: SavedTrack,
+    pub played_at: DateTime<Utc>,
+}
+
+/// A guild's queue as it was when last saved, so it survives restarts.
+#[derive(Debug, Clone)]
+pub struct QueueSnapshot {
+    pub guild_id: GuildId,
+    /// Voice channel the bot was playing in.
+    pub channel_id: Option<ChannelId>,
+    pub loop_mode: LoopMode,
+    pub fair_queue: bool,
+    /// How far into the first track playback was.
+    pub position: Duration,
+    pub tracks: Vec<SavedTrack>,
+}
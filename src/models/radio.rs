@@ -0,0 +1,10 @@
+use serenity::model::prelude::*;
+
+/// An internet radio station saved in a guild, played with `/radio play`.
+#[derive(Debug, Clone)]
+pub struct Station {
+    pub guild_id: GuildId,
+    pub name: String,
+    pub url: String,
+    pub added_by: UserId,
+}
@@ -0,0 +1,270 @@
+use crate::commands::wotd::word_embed;
+use crate::db::guilds::GuildStore;
+use crate::models::{guild::Guild, word};
+use anyhow::{Context, Result};
+use chrono::{prelude::*, LocalResult, TimeDelta};
+use chrono_tz::Tz;
+use serenity::builder::CreateMessage;
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Upper bound between checks, so configuration changes are picked up.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+/// Wait before retrying a failed post, doubled after every further failure.
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+/// Failed attempts after which a day's post is given up on.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Failed attempts at a guild's post for one local date.
+struct Failures {
+    date: NaiveDate,
+    attempts: u32,
+    retry_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Slot {
+    /// The post for this local date should go out now.
+    Due(NaiveDate),
+    /// Nothing to do until this instant.
+    At(DateTime<Utc>),
+}
+
+/// Runs forever, posting the Word of the Day into each configured guild's channel.
+pub async fn run(http: Arc<Http>, guilds: Arc<GuildStore>) {
+    info!("Starting Word of the Day scheduler");
+
+    let mut failures: HashMap<GuildId, Failures> = HashMap::new();
+    loop {
+        let mut sleep_for = MAX_SLEEP;
+        for guild in guilds.all().await.iter().filter(|g| g.setup) {
+            let (tz, time) = match (guild.timezone(), guild.wotd_time()) {
+                (Ok(tz), Ok(time)) => (tz, time),
+                (Err(e), _) | (_, Err(e)) => {
+                    warn!("Skipping guild {}: {:#}", guild.guild_id, e);
+                    continue;
+                }
+            };
+
+            let now = Utc::now();
+            let slot = next_slot(
+                now,
+                tz,
+                time,
+                guild.wotd_last_posted,
+                guild.wotd_configured_at,
+            );
+            match slot {
+                Slot::Due(date) => {
+                    let failed = failures
+                        .get(&guild.guild_id)
+                        .filter(|failed| failed.date == date);
+                    if let Some(failed) = failed {
+                        if failed.attempts >= MAX_ATTEMPTS {
+                            continue;
+                        }
+                        if let Ok(until) = (failed.retry_at - now).to_std() {
+                            sleep_for = sleep_for.min(until);
+                            continue;
+                        }
+                    }
+                    match post(&http, guild, date).await {
+                        Ok(_) => {
+                            info!("Posted Word of the Day in guild {}", guild.guild_id);
+                            failures.remove(&guild.guild_id);
+                            if let Err(e) = guilds.set_wotd_posted(guild.guild_id, date).await {
+                                error!("Couldn't record Word of the Day post: {:#}", e);
+                            }
+                        }
+                        Err(e) => {
+                            let attempts = failed.map_or(0, |failed| failed.attempts) + 1;
+                            error!(
+                                "Failed to post Word of the Day in guild {} (attempt {}): {:#}",
+                                guild.guild_id, attempts, e
+                            );
+                            if attempts >= MAX_ATTEMPTS {
+                                warn!(
+                                    "Giving up on {}'s Word of the Day in guild {}",
+                                    date, guild.guild_id
+                                );
+                            }
+                            failures.insert(
+                                guild.guild_id,
+                                Failures {
+                                    date,
+                                    attempts,
+                                    retry_at: now + retry_delay(attempts),
+                                },
+                            );
+                        }
+                    }
+                }
+                Slot::At(at) => {
+                    if let Ok(until) = (at - now).to_std() {
+                        sleep_for = sleep_for.min(until);
+                    }
+                }
+            }
+        }
+
+        tokio::time::sleep(sleep_for).await;
+    }
+}
+
+async fn post(http: &Http, guild: &Guild, date: NaiveDate) -> Result<Message> {
+    let channel_id = guild
+        .wotd_channel_id
+        .context("no Word of the Day channel configured")?;
+    let res = word::get_word().await?;
+    let msg = CreateMessage::new().embed(word_embed(&res, date));
+    Ok(channel_id.send_message(http, msg).await?)
+}
+
+/// How long to wait after a post failed `attempts` times in a row.
+fn retry_delay(attempts: u32) -> TimeDelta {
+    let delay = RETRY_DELAY * 2u32.pow(attempts.saturating_sub(1).min(10));
+    TimeDelta::from_std(delay).expect("retry delay in range")
+}
+
+/// Works out when a guild should next get its post, in its own timezone. A
+/// post missed earlier in the local day, e.g. because the bot was down at
+/// `wotd_time`, goes out late rather than not at all. Slots from before the
+/// schedule was last configured don't count, so setting it up late in the day
+/// waits for the next one.
+fn next_slot(
+    now: DateTime<Utc>,
+    tz: Tz,
+    time: NaiveTime,
+    last_posted: Option<NaiveDate>,
+    configured_at: Option<DateTime<Utc>>,
+) -> Slot {
+    let today = now.with_timezone(&tz).date_naive();
+    let todays_slot = localize(tz, today, time);
+    let scheduled = configured_at.is_none_or(|configured_at| todays_slot >= configured_at);
+    if last_posted < Some(today) && todays_slot <= now && scheduled {
+        return Slot::Due(today);
+    }
+
+    let mut date = today;
+    loop {
+        let at = localize(tz, date, time);
+        if at > now && last_posted < Some(date) {
+            return Slot::At(at);
+        }
+        date = date.succ_opt().expect("date in range");
+    }
+}
+
+/// Resolves a local wall-clock time into an instant, handling DST transitions:
+/// a repeated hour picks the first occurrence, a skipped hour moves forward.
+fn localize(tz: Tz, date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+    let mut local = date.and_time(time);
+    loop {
+        match tz.from_local_datetime(&local) {
+            LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => {
+                return dt.with_timezone(&Utc)
+            }
+            LocalResult::None => local += TimeDelta::minutes(30),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEW_YORK: Tz = chrono_tz::America::New_York;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn skipped_hour_moves_forward() {
+        // 02:30 doesn't exist on the night clocks go forward, 03:00 EDT does.
+        let at = localize(NEW_YORK, date(2024, 3, 10), time(2, 30));
+        assert_eq!(at, utc(2024, 3, 10, 7, 0));
+    }
+
+    #[test]
+    fn repeated_hour_picks_the_first() {
+        // 01:30 happens twice when clocks go back, first in EDT.
+        let at = localize(NEW_YORK, date(2024, 11, 3), time(1, 30));
+        assert_eq!(at, utc(2024, 11, 3, 5, 30));
+    }
+
+    #[test]
+    fn waits_for_todays_slot() {
+        let now = utc(2024, 6, 1, 12, 0);
+        let slot = next_slot(now, NEW_YORK, time(9, 0), Some(date(2024, 5, 31)), None);
+        assert_eq!(slot, Slot::At(utc(2024, 6, 1, 13, 0)));
+    }
+
+    #[test]
+    fn catches_up_a_post_missed_earlier_today() {
+        // Down from before 09:00 until 15:00 local time.
+        let now = utc(2024, 6, 1, 19, 0);
+        let slot = next_slot(now, NEW_YORK, time(9, 0), Some(date(2024, 5, 31)), None);
+        assert_eq!(slot, Slot::Due(date(2024, 6, 1)));
+    }
+
+    #[test]
+    fn posts_once_a_day() {
+        let now = utc(2024, 6, 1, 19, 0);
+        let slot = next_slot(now, NEW_YORK, time(9, 0), Some(date(2024, 6, 1)), None);
+        assert_eq!(slot, Slot::At(utc(2024, 6, 2, 13, 0)));
+    }
+
+    #[test]
+    fn slot_in_a_skipped_hour_is_due_once_clocks_move() {
+        let slot = next_slot(
+            utc(2024, 3, 10, 6, 59),
+            NEW_YORK,
+            time(2, 30),
+            Some(date(2024, 3, 9)),
+            None,
+        );
+        assert_eq!(slot, Slot::At(utc(2024, 3, 10, 7, 0)));
+        let slot = next_slot(
+            utc(2024, 3, 10, 7, 0),
+            NEW_YORK,
+            time(2, 30),
+            Some(date(2024, 3, 9)),
+            None,
+        );
+        assert_eq!(slot, Slot::Due(date(2024, 3, 10)));
+    }
+
+    #[test]
+    fn setting_up_after_todays_slot_waits_for_tomorrow() {
+        // Configured at 15:00 local time, six hours after the 09:00 slot.
+        let configured = utc(2024, 6, 1, 19, 0);
+        let now = utc(2024, 6, 1, 19, 1);
+        let slot = next_slot(now, NEW_YORK, time(9, 0), None, Some(configured));
+        assert_eq!(slot, Slot::At(utc(2024, 6, 2, 13, 0)));
+
+        // A slot after the change is still caught up on.
+        let configured = utc(2024, 6, 1, 12, 0);
+        let slot = next_slot(now, NEW_YORK, time(9, 0), None, Some(configured));
+        assert_eq!(slot, Slot::Due(date(2024, 6, 1)));
+    }
+
+    #[test]
+    fn retries_back_off() {
+        assert_eq!(retry_delay(1), TimeDelta::minutes(1));
+        assert_eq!(retry_delay(2), TimeDelta::minutes(2));
+        assert_eq!(retry_delay(4), TimeDelta::minutes(8));
+    }
+}
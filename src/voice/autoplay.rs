@@ -0,0 +1,141 @@
+use crate::db::Database;
+use crate::models::queue::SavedTrack;
+use crate::voice::cmds::{enqueue_info, source_for, TrackInfo};
+use crate::voice::{persist, playlist};
+use chrono::Utc;
+use reqwest::{Client as HttpClient, Url};
+use serenity::model::prelude::*;
+use songbird::input::YoutubeDl;
+use songbird::Songbird;
+use std::sync::Arc;
+use tracing::{debug, error, warn};
+
+/// How many of the latest played tracks autoplay won't pick again.
+const RECENT_LIMIT: usize = 50;
+/// Search results to pick from when the last track has no mix.
+const SEARCH_RESULTS: usize = 10;
+
+/// Queues a track related to the one that just finished, unless something
+/// else got queued while looking for it.
+pub(super) async fn play_related(
+    manager: Arc<Songbird>,
+    db: Database,
+    http_client: HttpClient,
+    guild_id: GuildId,
+    last: TrackInfo,
+    volume: f32,
+) {
+    let recent: Vec<_> = match db.history(guild_id) {
+        Ok(played) => played
+            .into_iter()
+            .take(RECENT_LIMIT)
+            .map(|played| played.track)
+            .collect(),
+        Err(why) => {
+            error!("Err reading play history {:?}", why);
+            Vec::new()
+        }
+    };
+    let Some(info) = related(&http_client, &last)
+        .await
+        .into_iter()
+        .find(|candidate| !is_recent(candidate, &last, &recent))
+    else {
+        debug!("Found nothing to autoplay after {}", last.title);
+        return;
+    };
+    let info = TrackInfo {
+        requester: None,
+        enqueued_at: Utc::now(),
+        autoplay: true,
+        ..info
+    };
+
+    let Some(handler_lock) = manager.get(guild_id) else {
+        return;
+    };
+    let mut handler = handler_lock.lock().await;
+    if !handler.queue().is_empty() {
+        return;
+    }
+    let source = match source_for(http_client, &info) {
+        Ok(source) => source,
+        Err(why) => {
+            warn!("Not autoplaying {}: {}", info.title, why);
+            return;
+        }
+    };
+    debug!("Autoplaying {}", info.title);
+    enqueue_info(&mut handler, guild_id, source, info, volume).await;
+    drop(handler);
+    persist::save(&db, &manager, guild_id).await;
+}
+
+/// Tracks like `last`, most related first: its YouTube mix if it has one,
+/// otherwise a search for its artist and title.
+async fn related(http_client: &HttpClient, last: &TrackInfo) -> Vec<TrackInfo> {
+    if let Some(id) = youtube_id(&last.url) {
+        let mix = format!("https://www.youtube.com/watch?v={}&list=RD{}", id, id);
+        match playlist::tracks(&mix).await {
+            Ok(tracks) if !tracks.is_empty() => return tracks,
+            Ok(_) => {}
+            Err(why) => warn!("Err loading mix {:?}", why),
+        }
+    }
+
+    let query = match &last.artist {
+        Some(artist) => format!("{} {}", artist, last.title),
+        None => last.title.clone(),
+    };
+    match YoutubeDl::new_search(http_client.clone(), query)
+        .search(Some(SEARCH_RESULTS))
+        .await
+    {
+        Ok(results) => results
+            .into_iter()
+            .filter(|result| result.source_url.is_some())
+            .map(TrackInfo::from)
+            .collect(),
+        Err(why) => {
+            error!("Err searching related tracks {:?}", why);
+            Vec::new()
+        }
+    }
+}
+
+/// Whether a candidate is the track that just ended or one played lately,
+/// going by its video or its title.
+fn is_recent(candidate: &TrackInfo, last: &TrackInfo, recent: &[SavedTrack]) -> bool {
+    let key = track_key(&candidate.url);
+    let title = candidate.title.trim().to_lowercase();
+    std::iter::once((last.url.as_str(), last.title.as_str()))
+        .chain(
+            recent
+                .iter()
+                .map(|track| (track.url.as_str(), track.title.as_str())),
+        )
+        .any(|(url, played)| track_key(url) == key || played.trim().to_lowercase() == title)
+}
+
+/// The video ID of YouTube links, which have several forms, or the link.
+fn track_key(url: &str) -> String {
+    youtube_id(url).unwrap_or_else(|| url.to_string())
+}
+
+fn youtube_id(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    let host = host.trim_start_matches("www.").trim_start_matches("m.");
+    match host {
+        "youtube.com" | "music.youtube.com" => url
+            .query_pairs()
+            .find(|(key, _)| key == "v")
+            .map(|(_, id)| id.into_owned()),
+        "youtu.be" => url
+            .path_segments()?
+            .next()
+            .filter(|id| !id.is_empty())
+            .map(str::to_string),
+        _ => None,
+    }
+}
@@ -1,15 +1,30 @@
+use crate::models::guild::{DEFAULT_IDLE_TIMEOUT_SECS, DEFAULT_VOLUME};
+use crate::models::queue::SavedTrack;
 use crate::voice::disconnect_handler::ChannelDisconnect;
+use crate::voice::dj::{self, Listener};
+use crate::voice::filters::{Filtered, TrackFilter, TrackFilterKey};
+use crate::voice::session::{self, SessionEvents};
+use crate::voice::sources::Sources;
+use crate::voice::{
+    controls, files, filters, history, lyrics, persist, playback, playlist, queue, radio, search,
+};
 use crate::{Context, Error};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
 use poise::ReplyHandle;
-use reqwest::Client as HttpClient;
+use reqwest::{Client as HttpClient, Url};
+use serenity::all::Mentionable;
 use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+use serenity::model::prelude::*;
+use serenity::prelude::Mutex;
 use serenity::prelude::TypeMapKey;
 use songbird::{
-    input::{Compose, YoutubeDl},
-    tracks::TrackHandle,
-    Songbird,
+    input::{AuxMetadata, Compose, File, HttpRequest, Input, YoutubeDl},
+    tracks::{Track, TrackHandle},
+    Call, Songbird,
 };
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{debug, error, info};
 
 pub struct VoiceHttpKey;
@@ -18,14 +33,144 @@ impl TypeMapKey for VoiceHttpKey {
     type Value = HttpClient;
 }
 
-struct TrackInfo;
-impl TypeMapKey for TrackInfo {
-    type Value = (String, String);
+/// Source of tracks uploaded to Discord as attachments.
+const DISCORD_SOURCE: &str = "Discord";
+
+pub(super) struct TrackInfoKey;
+impl TypeMapKey for TrackInfoKey {
+    type Value = TrackInfo;
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct TrackInfo {
+    pub title: String,
+    pub url: String,
+    pub artist: Option<String>,
+    pub duration: Option<Duration>,
+    pub thumbnail: Option<String>,
+    /// Who asked for the track, if anyone did.
+    pub requester: Option<UserId>,
+    /// Site the track comes from, like "YouTube".
+    pub source: String,
+    pub enqueued_at: DateTime<Utc>,
+    /// What a radio station says is on air, kept up to date while it plays.
+    pub stream_title: Option<String>,
+    /// Picked by autoplay when the queue ran out, rather than requested.
+    pub autoplay: bool,
+}
+
+impl TrackInfo {
+    /// Whether the track can be queued again later from what's saved of it.
+    /// Links to Discord attachments are signed and soon expire.
+    pub fn is_replayable(&self) -> bool {
+        !self.url.is_empty() && TrackInfo::source_name(&self.url) != DISCORD_SOURCE
+    }
+
+    /// Names the site behind a track URL.
+    pub fn source_name(url: &str) -> String {
+        if url.starts_with(files::LIBRARY_PREFIX) {
+            return "Library".to_string();
+        }
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let host = host.trim_start_matches("www.").trim_start_matches("m.");
+        match host {
+            "youtube.com" | "youtu.be" | "music.youtube.com" => "YouTube".to_string(),
+            "soundcloud.com" | "api.soundcloud.com" => "SoundCloud".to_string(),
+            "cdn.discordapp.com" | "media.discordapp.net" => DISCORD_SOURCE.to_string(),
+            "" => "Unknown".to_string(),
+            host => host.to_string(),
+        }
+    }
+}
+
+impl From<SavedTrack> for TrackInfo {
+    fn from(track: SavedTrack) -> Self {
+        Self {
+            title: track.title,
+            url: track.url,
+            artist: track.artist,
+            duration: track.duration,
+            thumbnail: track.thumbnail,
+            requester: track.requester,
+            source: track.source,
+            enqueued_at: track.enqueued_at,
+            stream_title: None,
+            autoplay: false,
+        }
+    }
+}
+
+impl From<TrackInfo> for SavedTrack {
+    fn from(info: TrackInfo) -> Self {
+        Self {
+            url: info.url,
+            title: info.title,
+            artist: info.artist,
+            duration: info.duration,
+            thumbnail: info.thumbnail,
+            requester: info.requester,
+            source: info.source,
+            enqueued_at: info.enqueued_at,
+        }
+    }
+}
+
+impl From<AuxMetadata> for TrackInfo {
+    fn from(metadata: AuxMetadata) -> Self {
+        let url = metadata
+            .source_url
+            .unwrap_or_else(|| "https://en.wikipedia.org/wiki/HTTP_404".to_string());
+        Self {
+            title: metadata.title.unwrap_or_else(|| "Title".to_string()),
+            source: Self::source_name(&url),
+            url,
+            artist: metadata.artist,
+            duration: metadata.duration,
+            thumbnail: metadata.thumbnail,
+            requester: None,
+            enqueued_at: Utc::now(),
+            stream_title: None,
+            autoplay: false,
+        }
+    }
 }
 
 #[poise::command(
     slash_command,
-    subcommands("join", "leave", "play", "pause", "resume", "stop", "skip", "info")
+    subcommands(
+        "join",
+        "leave",
+        "play",
+        "files::play_file",
+        "search::search",
+        "files::library",
+        "persist::restore",
+        "pause",
+        "resume",
+        "stop",
+        "skip",
+        "history::previous",
+        "info",
+        "history::history",
+        "history::replay",
+        "queue::remove",
+        "queue::move_track",
+        "queue::shuffle",
+        "queue::clear",
+        "queue::jump",
+        "queue::loop_mode",
+        "queue::fair",
+        "playback::seek",
+        "playback::forward",
+        "playback::rewind",
+        "playback::nowplaying",
+        "lyrics::lyrics",
+        "playback::volume",
+        "filters::filter"
+    )
 )]
 pub async fn voice(_: Context<'_>) -> Result<(), Error> {
     Ok(())
@@ -52,11 +197,17 @@ pub async fn join(ctx: Context<'_>) -> Result<(), Error> {
 #[poise::command(slash_command, guild_only)]
 async fn leave(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
 
     let manager = songbird::get(ctx.serenity_context())
         .await
         .expect("Songbird Voice client placed in at initialisation.")
         .clone();
+    if !dj::require_may_clear(ctx, &manager).await {
+        return Ok(());
+    }
     let has_handler = manager.get(guild_id).is_some();
 
     if has_handler {
@@ -68,6 +219,10 @@ async fn leave(ctx: Context<'_>) -> Result<(), Error> {
             handler.remove_all_global_events();
             info!("removing handlers");
         }
+        ChannelDisconnect::forget(guild_id).await;
+        if let Err(why) = ctx.data().db.delete_queue(guild_id) {
+            error!("Err deleting saved queue {:?}", why);
+        }
 
         check_msg(ctx.say("Left Voice Channel.").await);
     } else {
@@ -79,7 +234,11 @@ async fn leave(ctx: Context<'_>) -> Result<(), Error> {
 
 /// Play an audio track by providing a link or search query
 #[poise::command(slash_command, guild_only)]
-async fn play(ctx: Context<'_>, song: String) -> Result<(), Error> {
+async fn play(
+    ctx: Context<'_>,
+    #[description = "Link or search, or start with yt:, sc: or file: to pick where from"]
+    song: String,
+) -> Result<(), Error> {
     ctx.defer().await?;
 
     let manager = songbird::get(ctx.serenity_context())
@@ -96,43 +255,49 @@ async fn play(ctx: Context<'_>, song: String) -> Result<(), Error> {
     }
 
     if let Some(handler_lock) = manager.get(guild_id) {
-        let _dch = ChannelDisconnect::new(manager.clone(), guild_id)
-            .register_handler(&handler_lock)
-            .await;
-        let mut handler = handler_lock.lock().await;
+        if playlist::is_playlist(&song) {
+            playlist::enqueue(ctx, &manager, &handler_lock, &song).await?;
+            controls::show(ctx, manager.clone()).await;
+            return Ok(());
+        }
 
         // TODO: make this faster with less cloning
         let http_client = get_http_client(&ctx).await;
 
         let is_url = song.starts_with("http");
-        let mut source = if is_url {
-            YoutubeDl::new(http_client, song)
-        } else {
-            YoutubeDl::new_search(http_client, song)
-        };
-
-        debug!("Source: {source:?}");
-        let handle = handler.enqueue_input(source.clone().into()).await;
-        if let Ok(metadata) = source.aux_metadata().await {
-            debug!("metadata: {metadata:?}");
-            let url = match metadata.source_url {
-                Some(url) => url,
-                None => "https://en.wikipedia.org/wiki/HTTP_404".to_string(),
-            };
-            let title = match metadata.title {
-                Some(title) => title,
-                None => "Title".to_string(),
-            };
-            handle
-                .typemap()
-                .write()
+        let is_video_site = matches!(
+            TrackInfo::source_name(&song).as_str(),
+            "YouTube" | "SoundCloud"
+        );
+        // Links that look like streams are asked about first, any other link
+        // only once yt-dlp can't make sense of it.
+        let may_be_stream = is_url && !is_video_site;
+        let looks_like_stream = may_be_stream && radio::looks_like_stream(&song);
+        if looks_like_stream {
+            if let Some(stream) = radio::detect(&http_client, &song).await {
+                return radio::play_stream(ctx, stream, None).await;
+            }
+        }
+        match Sources::new(http_client.clone()).resolve(&song).await {
+            Ok(resolved) => {
+                enqueue(
+                    ctx,
+                    manager.clone(),
+                    &handler_lock,
+                    resolved.source,
+                    resolved.info,
+                )
                 .await
-                .insert::<TrackInfo>((title, url));
+            }
+            Err(why) => {
+                if may_be_stream && !looks_like_stream {
+                    if let Some(stream) = radio::detect(&http_client, &song).await {
+                        return radio::play_stream(ctx, stream, None).await;
+                    }
+                }
+                check_msg(ctx.say(why.to_string()).await)
+            }
         }
-        let embed = song_embed(&mut source, handler.queue().len()).await;
-
-        let msg = poise::CreateReply::default().embed(embed);
-        check_msg(ctx.send(msg).await);
     } else {
         check_msg(ctx.say("Not in a voice channel.").await);
     }
@@ -140,10 +305,77 @@ async fn play(ctx: Context<'_>, song: String) -> Result<(), Error> {
     Ok(())
 }
 
+/// Queues a single track for the caller, replies with its place in the queue
+/// and brings up the now-playing message.
+pub(super) async fn enqueue(
+    ctx: Context<'_>,
+    manager: Arc<Songbird>,
+    handler_lock: &Arc<Mutex<Call>>,
+    source: Box<dyn Compose>,
+    info: TrackInfo,
+) {
+    let info = TrackInfo {
+        requester: Some(ctx.author().id),
+        enqueued_at: Utc::now(),
+        ..info
+    };
+
+    let guild_id = ctx.guild_id().unwrap();
+    let volume = guild_volume(ctx, guild_id).await;
+    let mut handler = handler_lock.lock().await;
+    let handle = enqueue_info(&mut handler, guild_id, source, info.clone(), volume).await;
+    if session::get(guild_id).await.lock().await.fair_queue {
+        queue::interleave_by_requester(handler.queue()).await;
+    }
+    let position = handler
+        .queue()
+        .current_queue()
+        .iter()
+        .position(|track| track.uuid() == handle.uuid())
+        .map_or(0, |i| i + 1);
+    drop(handler);
+
+    let embed = song_embed(&info, position);
+    let msg = poise::CreateReply::default().embed(embed);
+    check_msg(ctx.send(msg).await);
+    persist::save(&ctx.data().db, &manager, guild_id).await;
+    controls::show(ctx, manager).await;
+}
+
+/// Queues a track whose metadata is already known, tagging it with `info`
+/// and playing it through the guild's filter.
+pub(super) async fn enqueue_info(
+    handler: &mut Call,
+    guild_id: GuildId,
+    source: Box<dyn Compose>,
+    info: TrackInfo,
+    volume: f32,
+) -> TrackHandle {
+    // Preload the next track shortly before this one ends, as `enqueue_input`
+    // would, without asking yt-dlp for the metadata a second time.
+    let preload = info
+        .duration
+        .map(|duration| duration.saturating_sub(Duration::from_secs(5)));
+    let filter = TrackFilter::new(session::get(guild_id).await.lock().await.filter.clone());
+    let input = Input::Lazy(Box::new(Filtered::new(source, filter.clone())));
+    let handle = handler.enqueue_with_preload(Track::from(input), preload);
+    if let Err(why) = handle.set_volume(volume) {
+        error!("Err setting volume {:?}", why);
+    }
+    let mut typemap = handle.typemap().write().await;
+    typemap.insert::<TrackInfoKey>(info);
+    typemap.insert::<TrackFilterKey>(filter);
+    drop(typemap);
+    handle
+}
+
 /// Pauses the current audio track
 #[poise::command(slash_command, guild_only)]
 async fn pause(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
 
     let manager = songbird::get(ctx.serenity_context())
         .await
@@ -170,6 +402,9 @@ async fn pause(ctx: Context<'_>) -> Result<(), Error> {
 #[poise::command(slash_command, guild_only)]
 async fn resume(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
 
     let manager = songbird::get(ctx.serenity_context())
         .await
@@ -196,15 +431,23 @@ async fn resume(ctx: Context<'_>) -> Result<(), Error> {
 #[poise::command(slash_command, guild_only)]
 async fn stop(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
 
     let manager = songbird::get(ctx.serenity_context())
         .await
         .expect("Songbird Voice client placed in at initialisation.")
         .clone();
+    if !dj::require_may_clear(ctx, &manager).await {
+        return Ok(());
+    }
 
     if let Some(handler_lock) = manager.get(guild_id) {
         let handler = handler_lock.lock().await;
 
+        // Forget the current track first, so queue loop mode doesn't put it back.
+        session::forget_current(&ctx.data().db, guild_id).await;
         handler.queue().stop();
 
         check_msg(ctx.say("stopping song and clearing queue").await);
@@ -219,29 +462,33 @@ async fn stop(ctx: Context<'_>) -> Result<(), Error> {
 #[poise::command(slash_command, guild_only)]
 async fn skip(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
 
     let manager = songbird::get(ctx.serenity_context())
         .await
         .expect("Songbird Voice client placed in at initialisation.")
         .clone();
 
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let handler = handler_lock.lock().await;
-
-        if let Err(why) = handler.queue().skip() {
-            error!("Err skip source {:?}", why);
-            return Ok(());
-        }
-
-        check_msg(ctx.say("Skipped audio track.").await);
-    } else {
-        check_msg(ctx.say("Not in a voice channel.").await);
-    }
+    let Some(listener) = Listener::from_ctx(ctx).await else {
+        return Ok(());
+    };
+    let config = ctx.data().guilds.get(guild_id).await;
+    let reply = dj::skip(
+        &ctx.serenity_context().cache,
+        &manager,
+        config.as_ref(),
+        guild_id,
+        &listener,
+    )
+    .await;
+    check_msg(ctx.say(reply).await);
 
     Ok(())
 }
 
-/// Display a list of the current audio tracks
+/// Display the queue, a page at a time
 #[poise::command(slash_command, guild_only)]
 async fn info(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
@@ -251,37 +498,9 @@ async fn info(ctx: Context<'_>) -> Result<(), Error> {
         .expect("Songbird Voice client placed in at initialisation.")
         .clone();
 
-    let footer = CreateEmbedFooter::new("rauly.rs");
-    let mut embed = CreateEmbed::new()
-        .colour(0xeb984e)
-        .title("Music Queue")
-        .footer(footer);
-
     if let Some(handler_lock) = manager.get(guild_id) {
-        let handler = handler_lock.lock().await;
-
-        // TODO: handle empty case
-        let list = handler.queue().current_queue();
-        for (i, track) in list.iter().take(10).enumerate() {
-            let metadata = get_metadata(track).await;
-            info!("Position: {}, Url: {} Title: {}", i, metadata.1, metadata.0);
-            if i == 0 {
-                embed = embed.field(
-                    "Now Playing:".to_string(),
-                    format!("[{}]({})", metadata.0, metadata.1),
-                    false,
-                );
-            } else {
-                embed = embed.field(
-                    String::new(),
-                    format!("**{}**. [{}]({})", i, metadata.0, metadata.1),
-                    false,
-                );
-            }
-        }
-
-        let msg = poise::CreateReply::default().embed(embed);
-        check_msg(ctx.send(msg).await);
+        let list = handler_lock.lock().await.queue().current_queue();
+        queue::show(ctx, &list).await?;
     } else {
         check_msg(ctx.say("Not in a voice channel.").await);
     }
@@ -289,7 +508,7 @@ async fn info(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-async fn join_vc(ctx: Context<'_>, manager: Arc<Songbird>) -> Result<(), String> {
+pub(super) async fn join_vc(ctx: Context<'_>, manager: Arc<Songbird>) -> Result<(), String> {
     let (guild_id, channel_id) = {
         let guild = ctx.guild().unwrap();
 
@@ -306,67 +525,163 @@ async fn join_vc(ctx: Context<'_>, manager: Arc<Songbird>) -> Result<(), String>
         }
     };
 
-    if let Err(why) = manager.join(guild_id, connect_to).await {
-        debug!("Failed to join vc: {}", why);
-        return Err("Failed to join voice channel.".to_string());
+    join_channel(ctx, manager, guild_id, connect_to).await
+}
+
+/// Joins a given voice channel and sets up the call's event handlers.
+pub(super) async fn join_channel(
+    ctx: Context<'_>,
+    manager: Arc<Songbird>,
+    guild_id: GuildId,
+    connect_to: ChannelId,
+) -> Result<(), String> {
+    match manager.join(guild_id, connect_to).await {
+        Ok(handler_lock) => {
+            let timeout = match ctx.data().guilds.get(guild_id).await {
+                Some(guild) => guild.idle_timeout_secs,
+                None => DEFAULT_IDLE_TIMEOUT_SECS,
+            };
+            ChannelDisconnect::new(manager.clone(), guild_id, Duration::from_secs(timeout))
+                .register_handler(&handler_lock)
+                .await;
+            SessionEvents::new(
+                manager.clone(),
+                guild_id,
+                ctx.data().db.clone(),
+                ctx.data().guilds.clone(),
+                ctx.serenity_context().http.clone(),
+                get_http_client(&ctx).await,
+            )
+            .register_handler(&handler_lock)
+            .await;
+        }
+        Err(why) => {
+            debug!("Failed to join vc: {}", why);
+            return Err("Failed to join voice channel.".to_string());
+        }
     }
 
-    return Ok(());
+    Ok(())
 }
 
-async fn get_metadata(track_handle: &TrackHandle) -> (String, String) {
+pub(super) async fn get_metadata(track_handle: &TrackHandle) -> TrackInfo {
     let typemap = track_handle.typemap().read().await;
     typemap
-        .get::<TrackInfo>()
+        .get::<TrackInfoKey>()
         .cloned()
-        .expect("This shouldn't be empty")
+        .unwrap_or_else(|| TrackInfo {
+            title: "Unknown track".to_string(),
+            url: String::new(),
+            artist: None,
+            duration: None,
+            thumbnail: None,
+            requester: None,
+            source: "Unknown".to_string(),
+            enqueued_at: Utc::now(),
+            stream_title: None,
+            autoplay: false,
+        })
+}
+
+/// The guild's default volume, as a gain for `TrackHandle::set_volume`.
+pub(super) async fn guild_volume(ctx: Context<'_>, guild_id: GuildId) -> f32 {
+    let volume = match ctx.data().guilds.get(guild_id).await {
+        Some(guild) => guild.volume,
+        None => DEFAULT_VOLUME,
+    };
+    volume as f32 / 100.0
 }
 
-async fn get_http_client(ctx: &Context<'_>) -> HttpClient {
+/// Picks how to stream a track: library files from disk, Discord attachments
+/// and radio stations over plain HTTP and everything else through yt-dlp.
+/// Fails for library files that were removed since the track was saved.
+pub(super) fn source_for(http_client: HttpClient, info: &TrackInfo) -> Result<Box<dyn Compose>> {
+    let url = info.url.as_str();
+    if info.source == radio::RADIO_SOURCE {
+        return Ok(radio::source(http_client, url));
+    }
+    if let Some(path) = url.strip_prefix(files::LIBRARY_PREFIX) {
+        let Some(full) = files::resolve(path) else {
+            bail!("`{}` is no longer in the music library.", path);
+        };
+        return Ok(Box::new(File::new(full)));
+    }
+    if TrackInfo::source_name(url) == DISCORD_SOURCE {
+        return Ok(Box::new(HttpRequest::new(http_client, url.to_string())));
+    }
+    Ok(Box::new(YoutubeDl::new(http_client, url.to_string())))
+}
+
+/// A markdown link to a track, or just its title when it has no web page.
+pub(super) fn title_link(title: &str, url: &str) -> String {
+    if url.starts_with("http") {
+        format!("[{}]({})", title, url)
+    } else {
+        title.to_string()
+    }
+}
+
+pub(super) async fn get_http_client(ctx: &Context<'_>) -> HttpClient {
     let data = ctx.serenity_context().data.read().await;
     data.get::<VoiceHttpKey>()
         .cloned()
         .expect("Guaranteed to exist in the typemap")
 }
 
-fn check_msg(result: Result<ReplyHandle, serenity::Error>) {
+pub(super) fn check_msg(result: Result<ReplyHandle, serenity::Error>) {
     if let Err(why) = result {
         error!("Error sending message: {:?}", why);
     }
 }
 
-async fn song_embed(current_track: &mut impl Compose, postion: usize) -> CreateEmbed {
+pub(super) fn song_embed(info: &TrackInfo, postion: usize) -> CreateEmbed {
+    track_embed(info).field("Position in Queue", format!("{}", postion), false)
+}
+
+/// Embed describing a track, shared by `play` and the now-playing message.
+pub(super) fn track_embed(info: &TrackInfo) -> CreateEmbed {
     let footer = CreateEmbedFooter::new("rauly.rs");
-    let mut embed = CreateEmbed::new().colour(0xeb984e);
-    if let Ok(metadata) = current_track.aux_metadata().await {
-        info!("metadata: {metadata:?}");
-        if let Some(title) = metadata.title {
-            embed = embed.title(format!("rauly.rs | {}", title));
-        }
-        if let Some(artist) = metadata.artist {
-            embed = embed.field("Artist", artist.to_string(), true);
-        }
-        if let Some(track) = metadata.track {
-            embed = embed.field("Track", track.to_string(), true);
-        }
-        if let Some(track_url) = metadata.source_url {
-            embed = embed.url(track_url);
-        }
-        if let Some(track_len) = metadata.duration {
-            let track_min = (track_len.as_secs() / 60) % 60;
-            let track_secs = track_len.as_secs() % 60;
-            embed = embed.field(
-                "Song Duration",
-                format!("{}:{:0>2}", track_min, track_secs),
-                false,
-            );
-        }
-        if let Some(thumbnail) = metadata.thumbnail {
-            embed = embed.image(thumbnail);
-        }
+    let mut embed = CreateEmbed::new()
+        .colour(0xeb984e)
+        .title(format!("rauly.rs | {}", info.title))
+        .footer(footer);
+    if let Some(artist) = &info.artist {
+        embed = embed.field("Artist", artist, true);
+    }
+    if info.url.starts_with("http") {
+        embed = embed.url(&info.url);
+    }
+    if let Some(track_len) = info.duration {
+        embed = embed.field("Song Duration", format_duration(track_len), false);
+    } else if info.source == radio::RADIO_SOURCE {
+        embed = embed.field("Song Duration", "🔴 Live", false);
+    }
+    if let Some(stream_title) = &info.stream_title {
+        embed = embed.field("On Air", stream_title, false);
+    }
+    if info.autoplay {
+        embed = embed.field("Requested by", "🔁 Autoplay", true);
+    } else if let Some(requester) = info.requester {
+        embed = embed.field("Requested by", requester.mention().to_string(), true);
+    }
+    embed = embed.field("Source", &info.source, true).field(
+        "Queued",
+        format!("<t:{}:R>", info.enqueued_at.timestamp()),
+        true,
+    );
+    if let Some(thumbnail) = &info.thumbnail {
+        embed = embed.image(thumbnail);
     }
-
     embed
-        .field("Position in Queue", format!("{}", postion), false)
-        .footer(footer)
+}
+
+/// Formats a duration as `m:ss`, or `h:mm:ss` for long tracks.
+pub(super) fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, mins, secs) = (secs / 3600, (secs / 60) % 60, secs % 60);
+    if hours > 0 {
+        format!("{}:{:0>2}:{:0>2}", hours, mins, secs)
+    } else {
+        format!("{}:{:0>2}", mins, secs)
+    }
 }
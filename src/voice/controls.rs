@@ -0,0 +1,263 @@
+use crate::db::guilds::GuildStore;
+use crate::db::Database;
+use crate::voice::cmds::{get_metadata, track_embed};
+use crate::voice::dj::{self, Listener};
+use crate::voice::persist;
+use crate::voice::queue::shuffle_upcoming;
+use crate::voice::session::{self, LoopMode};
+use crate::Context;
+use poise::futures_util::StreamExt;
+use poise::ChoiceParameter;
+use serenity::all::{
+    ButtonStyle, ComponentInteraction, ComponentInteractionCollector, CreateActionRow,
+    CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage,
+    EditMessage,
+};
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use serenity::prelude::Context as SerenityContext;
+use songbird::tracks::PlayMode;
+use songbird::Songbird;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::error;
+
+const PAUSE: &str = "np_pause";
+const SKIP: &str = "np_skip";
+const STOP: &str = "np_stop";
+const LOOP: &str = "np_loop";
+const SHUFFLE: &str = "np_shuffle";
+
+/// The message showing what is playing in a guild, edited as tracks change.
+pub struct NowPlayingMessage {
+    channel_id: ChannelId,
+    message_id: MessageId,
+    collector: JoinHandle<()>,
+}
+
+impl Drop for NowPlayingMessage {
+    fn drop(&mut self) {
+        self.collector.abort();
+    }
+}
+
+/// Posts the now-playing message in the command's channel, or refreshes it if
+/// the guild already has one.
+pub async fn show(ctx: Context<'_>, manager: Arc<Songbird>) {
+    let guild_id = ctx.guild_id().unwrap();
+    let session = session::get(guild_id).await;
+    if session.lock().await.now_playing_message.is_some() {
+        refresh(ctx.http(), &manager, guild_id).await;
+        return;
+    }
+
+    let (embed, components) = render(&manager, guild_id).await;
+    let msg = CreateMessage::new().embed(embed).components(components);
+    let message = match ctx.channel_id().send_message(ctx.http(), msg).await {
+        Ok(message) => message,
+        Err(why) => {
+            error!("Err sending now playing message {:?}", why);
+            return;
+        }
+    };
+
+    let collector = tokio::spawn(collect(
+        ctx.serenity_context().clone(),
+        manager,
+        ctx.data().db.clone(),
+        ctx.data().guilds.clone(),
+        guild_id,
+        message.id,
+    ));
+    session.lock().await.now_playing_message = Some(NowPlayingMessage {
+        channel_id: message.channel_id,
+        message_id: message.id,
+        collector,
+    });
+}
+
+/// Edits the now-playing message to match the current playback state.
+pub async fn refresh(http: &Http, manager: &Songbird, guild_id: GuildId) {
+    let ids = session::get(guild_id)
+        .await
+        .lock()
+        .await
+        .now_playing_message
+        .as_ref()
+        .map(|msg| (msg.channel_id, msg.message_id));
+    let Some((channel_id, message_id)) = ids else {
+        return;
+    };
+
+    let (embed, components) = render(manager, guild_id).await;
+    let edit = EditMessage::new().embed(embed).components(components);
+    if let Err(why) = channel_id.edit_message(http, message_id, edit).await {
+        error!("Err editing now playing message {:?}", why);
+    }
+}
+
+async fn collect(
+    ctx: SerenityContext,
+    manager: Arc<Songbird>,
+    db: Database,
+    guilds: Arc<GuildStore>,
+    guild_id: GuildId,
+    message_id: MessageId,
+) {
+    let mut interactions = ComponentInteractionCollector::new(&ctx)
+        .message_id(message_id)
+        .stream();
+    while let Some(interaction) = interactions.next().await {
+        let response = match press(&ctx, &manager, &db, &guilds, guild_id, &interaction).await {
+            Some(notice) => CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(notice)
+                    .ephemeral(true),
+            ),
+            None => {
+                persist::save(&db, &manager, guild_id).await;
+                let (embed, components) = render(&manager, guild_id).await;
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .embed(embed)
+                        .components(components),
+                )
+            }
+        };
+        if let Err(why) = interaction.create_response(&ctx, response).await {
+            error!("Err responding to button {:?}", why);
+        }
+    }
+}
+
+/// Applies the button that was pressed, following the same DJ rules as the
+/// commands. Returns a notice for the presser if there's something to say.
+async fn press(
+    ctx: &SerenityContext,
+    manager: &Songbird,
+    db: &Database,
+    guilds: &GuildStore,
+    guild_id: GuildId,
+    interaction: &ComponentInteraction,
+) -> Option<String> {
+    let member = interaction.member.as_ref()?;
+    let config = guilds.get(guild_id).await;
+    let listener = Listener::new(&ctx.cache, config.as_ref(), guild_id, member);
+    let bot_channel = dj::bot_channel(manager, guild_id).await?;
+    if listener.channel_id != Some(bot_channel) {
+        return Some("You need to be in my voice channel to do that.".to_string());
+    }
+    match interaction.data.custom_id.as_str() {
+        SKIP => {
+            let notice = dj::skip(&ctx.cache, manager, config.as_ref(), guild_id, &listener).await;
+            return Some(notice);
+        }
+        STOP if !dj::may_clear(&listener, manager, guild_id).await => {
+            return Some("Only DJs can do that while others have tracks queued.".to_string());
+        }
+        _ => {}
+    }
+
+    let handler_lock = manager.get(guild_id)?;
+    let handler = handler_lock.lock().await;
+    let queue = handler.queue();
+
+    let result = match interaction.data.custom_id.as_str() {
+        PAUSE => match queue.current() {
+            Some(track) => match track.get_info().await {
+                Ok(state) if state.playing == PlayMode::Pause => queue.resume(),
+                Ok(_) => queue.pause(),
+                Err(why) => Err(why),
+            },
+            None => Ok(()),
+        },
+        STOP => {
+            // Forget the current track first, so queue loop mode doesn't put it back.
+            session::forget_current(db, guild_id).await;
+            queue.stop();
+            Ok(())
+        }
+        LOOP => {
+            let mode = session::get(guild_id).await.lock().await.loop_mode;
+            let next = match mode {
+                LoopMode::Off => LoopMode::Track,
+                LoopMode::Track => LoopMode::Queue,
+                LoopMode::Queue => LoopMode::Off,
+            };
+            session::set_loop_mode(guild_id, queue.current(), next).await;
+            Ok(())
+        }
+        SHUFFLE => {
+            shuffle_upcoming(queue);
+            Ok(())
+        }
+        _ => Ok(()),
+    };
+    if let Err(why) = result {
+        error!(
+            "Err handling {} button {:?}",
+            interaction.data.custom_id, why
+        );
+    }
+    None
+}
+
+async fn render(manager: &Songbird, guild_id: GuildId) -> (CreateEmbed, Vec<CreateActionRow>) {
+    let (track, loop_mode) = {
+        let session = session::get(guild_id).await;
+        let session = session.lock().await;
+        (session.now_playing.clone(), session.loop_mode)
+    };
+    let upcoming = match manager.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.queue().len().saturating_sub(1),
+        None => 0,
+    };
+
+    let Some(track) = track else {
+        let embed = CreateEmbed::new()
+            .colour(0xeb984e)
+            .title("rauly.rs | Nothing is playing")
+            .footer(CreateEmbedFooter::new("rauly.rs"));
+        return (embed, buttons(false, loop_mode, false));
+    };
+
+    let paused = track
+        .get_info()
+        .await
+        .is_ok_and(|state| state.playing == PlayMode::Pause);
+    let embed = track_embed(&get_metadata(&track).await)
+        .field("Status", if paused { "Paused" } else { "Playing" }, true)
+        .field("Loop", loop_mode.name(), true)
+        .field("Up Next", format!("{} tracks", upcoming), true);
+    (embed, buttons(true, loop_mode, paused))
+}
+
+fn buttons(active: bool, loop_mode: LoopMode, paused: bool) -> Vec<CreateActionRow> {
+    let loop_style = match loop_mode {
+        LoopMode::Off => ButtonStyle::Secondary,
+        LoopMode::Track | LoopMode::Queue => ButtonStyle::Success,
+    };
+    let row = CreateActionRow::Buttons(vec![
+        CreateButton::new(PAUSE)
+            .label(if paused { "Resume" } else { "Pause" })
+            .style(ButtonStyle::Primary)
+            .disabled(!active),
+        CreateButton::new(SKIP)
+            .label("Skip")
+            .style(ButtonStyle::Secondary)
+            .disabled(!active),
+        CreateButton::new(STOP)
+            .label("Stop")
+            .style(ButtonStyle::Danger)
+            .disabled(!active),
+        CreateButton::new(LOOP)
+            .label(format!("Loop: {}", loop_mode.name()))
+            .style(loop_style),
+        CreateButton::new(SHUFFLE)
+            .label("Shuffle")
+            .style(ButtonStyle::Secondary)
+            .disabled(!active),
+    ]);
+    vec![row]
+}
@@ -1,44 +1,103 @@
+use crate::voice::session;
 use serenity::async_trait;
 use serenity::model::prelude::*;
 use serenity::prelude::RwLock;
 use serenity::prelude::*;
-use songbird::{Call, Event, EventContext, EventHandler, Songbird};
+use songbird::{tracks::PlayMode, Call, Event, EventContext, EventHandler, Songbird, TrackEvent};
 use std::{
+    collections::HashMap,
     sync::{Arc, LazyLock},
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tracing::info;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
 
-static HANDLER_ADDED: LazyLock<RwLock<bool>> = LazyLock::new(|| RwLock::new(false));
-const TIMEOUT_SECS: u64 = 420;
+/// When each guild's call went idle, or `None` while something is playing.
+/// A guild only has an entry while its disconnect handler is registered.
+static IDLE_SINCE: LazyLock<RwLock<HashMap<GuildId, Option<Instant>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+const CHECK_INTERVAL_SECS: u64 = 30;
+
+/// Guilds whose voice channel has no humans left, with the pending leave.
+static EMPTY_CHANNELS: LazyLock<Mutex<HashMap<GuildId, EmptyChannel>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct EmptyChannel {
+    leave: JoinHandle<()>,
+    /// Whether the bot paused the queue itself, and so should resume it.
+    paused: bool,
+}
 
 #[derive(Clone)]
 pub struct ChannelDisconnect {
     manager: Arc<Songbird>,
     guild_id: GuildId,
+    timeout: Duration,
 }
 
 impl ChannelDisconnect {
-    pub fn new(manager: Arc<Songbird>, guild_id: GuildId) -> Self {
-        Self { manager, guild_id }
+    pub fn new(manager: Arc<Songbird>, guild_id: GuildId, timeout: Duration) -> Self {
+        Self {
+            manager,
+            guild_id,
+            timeout,
+        }
     }
 
     pub async fn register_handler(&self, handler_lock: &Arc<Mutex<Call>>) {
-        if !*HANDLER_ADDED.read().await {
-            info!("Register handler for disconnect");
-            let mut ha = HANDLER_ADDED.write().await;
-            *ha = true;
-            let mut handler = handler_lock.lock().await;
-            handler.add_global_event(
-                Event::Periodic(Duration::from_secs(TIMEOUT_SECS), None),
-                self.clone(),
-            );
-        } else {
-            info!("No handler");
+        let mut idle = IDLE_SINCE.write().await;
+        if idle.contains_key(&self.guild_id) {
+            return;
+        }
+        info!("Register disconnect handler for guild {}", self.guild_id);
+        idle.insert(self.guild_id, Some(Instant::now()));
+
+        let mut handler = handler_lock.lock().await;
+        let activity = TrackActivity {
+            guild_id: self.guild_id,
+        };
+        handler.add_global_event(Event::Track(TrackEvent::Play), activity.clone());
+        handler.add_global_event(Event::Track(TrackEvent::End), activity);
+        handler.add_global_event(
+            Event::Periodic(Duration::from_secs(CHECK_INTERVAL_SECS), None),
+            self.clone(),
+        );
+    }
+
+    /// Drops the idle tracking and playback session of a guild whose call has
+    /// been removed.
+    pub async fn forget(guild_id: GuildId) {
+        IDLE_SINCE.write().await.remove(&guild_id);
+        session::remove(guild_id).await;
+        if let Some(empty) = EMPTY_CHANNELS.lock().await.remove(&guild_id) {
+            empty.leave.abort();
+        }
+    }
+
+    /// (Re)starts the idle countdown for a guild. Tracks removed from the queue
+    /// also end, so this can fire while something plays; `disconnect` only
+    /// leaves once the queue is really empty.
+    pub async fn mark_idle(guild_id: GuildId) {
+        if let Some(since) = IDLE_SINCE.write().await.get_mut(&guild_id) {
+            *since = Some(Instant::now());
+        }
+    }
+
+    pub async fn mark_active(guild_id: GuildId) {
+        if let Some(since) = IDLE_SINCE.write().await.get_mut(&guild_id) {
+            *since = None;
         }
     }
 
     async fn disconnect(&self) {
+        let timed_out = match IDLE_SINCE.read().await.get(&self.guild_id) {
+            Some(Some(since)) => since.elapsed() >= self.timeout,
+            _ => false,
+        };
+        if !timed_out {
+            return;
+        }
+
         let should_close = match self.manager.get(self.guild_id) {
             None => false,
             Some(handler_lock) => {
@@ -48,12 +107,10 @@ impl ChannelDisconnect {
         };
 
         if should_close {
-            info!("Leaving voice channel.");
+            info!("Leaving voice channel in guild {}.", self.guild_id);
             let _dc = self.manager.remove(self.guild_id).await;
+            Self::forget(self.guild_id).await;
             info!("Left voice channel.");
-            {
-                *HANDLER_ADDED.write().await = false;
-            }
         }
     }
 }
@@ -61,8 +118,99 @@ impl ChannelDisconnect {
 #[async_trait]
 impl EventHandler for ChannelDisconnect {
     async fn act(&self, _: &EventContext<'_>) -> Option<Event> {
-        info!("Checking if bot is active");
         self.disconnect().await;
         None
     }
 }
+
+/// Feeds track starts and ends into the idle countdown. Kept apart from
+/// `ChannelDisconnect` since periodic events also get a track context.
+#[derive(Clone)]
+struct TrackActivity {
+    guild_id: GuildId,
+}
+
+#[async_trait]
+impl EventHandler for TrackActivity {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(tracks) = ctx {
+            if tracks.iter().any(|(state, _)| !state.playing.is_done()) {
+                ChannelDisconnect::mark_active(self.guild_id).await;
+            } else {
+                ChannelDisconnect::mark_idle(self.guild_id).await;
+            }
+        }
+        None
+    }
+}
+
+/// Pauses and schedules a leave when the bot is left alone in its voice channel,
+/// and undoes both if a human joins back before the grace period runs out.
+pub async fn voice_state_update(ctx: &Context, guild_id: GuildId, grace: Duration) {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+    let Some(handler_lock) = manager.get(guild_id) else {
+        return;
+    };
+    let Some(channel_id) = handler_lock.lock().await.current_channel() else {
+        return;
+    };
+    let channel_id = ChannelId::new(channel_id.0.get());
+
+    let humans = match ctx.cache.guild(guild_id) {
+        Some(guild) => guild
+            .voice_states
+            .values()
+            .filter(|state| state.channel_id == Some(channel_id))
+            .filter(|state| !is_bot(ctx, state))
+            .count(),
+        None => return,
+    };
+
+    let mut empty_channels = EMPTY_CHANNELS.lock().await;
+    if humans > 0 {
+        if let Some(empty) = empty_channels.remove(&guild_id) {
+            info!("Listener is back in guild {}, staying.", guild_id);
+            empty.leave.abort();
+            if empty.paused {
+                if let Err(why) = handler_lock.lock().await.queue().resume() {
+                    warn!("Err resuming source {:?}", why);
+                }
+            }
+        }
+        return;
+    }
+    if empty_channels.contains_key(&guild_id) {
+        return;
+    }
+
+    let mut paused = false;
+    if let Some(track) = handler_lock.lock().await.queue().current() {
+        if matches!(track.get_info().await, Ok(info) if info.playing == PlayMode::Play) {
+            paused = track.pause().is_ok();
+        }
+    }
+
+    info!(
+        "Voice channel in guild {} is empty, leaving in {}s.",
+        guild_id,
+        grace.as_secs()
+    );
+    let leave = tokio::spawn(async move {
+        tokio::time::sleep(grace).await;
+        EMPTY_CHANNELS.lock().await.remove(&guild_id);
+        info!("Leaving empty voice channel in guild {}.", guild_id);
+        let _dc = manager.remove(guild_id).await;
+        ChannelDisconnect::forget(guild_id).await;
+    });
+    empty_channels.insert(guild_id, EmptyChannel { leave, paused });
+}
+
+fn is_bot(ctx: &Context, state: &VoiceState) -> bool {
+    match &state.member {
+        Some(member) => member.user.bot,
+        None => ctx.cache.user(state.user_id).is_some_and(|user| user.bot),
+    }
+}
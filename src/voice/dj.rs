@@ -0,0 +1,172 @@
+use crate::models::guild::{Guild, DEFAULT_VOTE_SKIP_PERCENT};
+use crate::voice::cmds::{check_msg, get_metadata};
+use crate::voice::session;
+use crate::Context;
+use serenity::cache::Cache;
+use serenity::model::prelude::*;
+use songbird::Songbird;
+use std::collections::HashSet;
+use tracing::error;
+
+/// The member behind a command or button press, as far as the DJ rules care.
+pub(super) struct Listener {
+    pub user_id: UserId,
+    pub is_dj: bool,
+    pub channel_id: Option<ChannelId>,
+}
+
+impl Listener {
+    /// Server managers are always DJs, and so is anyone with the DJ role.
+    pub fn new(cache: &Cache, config: Option<&Guild>, guild_id: GuildId, member: &Member) -> Self {
+        let is_dj = member.permissions.is_some_and(|p| p.manage_guild())
+            || config
+                .and_then(|config| config.dj_role_id)
+                .is_some_and(|role_id| member.roles.contains(&role_id));
+        let channel_id = cache.guild(guild_id).and_then(|guild| {
+            guild
+                .voice_states
+                .get(&member.user.id)
+                .and_then(|state| state.channel_id)
+        });
+        Self {
+            user_id: member.user.id,
+            is_dj,
+            channel_id,
+        }
+    }
+
+    pub async fn from_ctx(ctx: Context<'_>) -> Option<Self> {
+        let guild_id = ctx.guild_id()?;
+        let member = ctx.author_member().await?;
+        let config = ctx.data().guilds.get(guild_id).await;
+        Some(Self::new(
+            &ctx.serenity_context().cache,
+            config.as_ref(),
+            guild_id,
+            &member,
+        ))
+    }
+}
+
+/// The voice channel the bot is connected to in a guild.
+pub(super) async fn bot_channel(manager: &Songbird, guild_id: GuildId) -> Option<ChannelId> {
+    let handler_lock = manager.get(guild_id)?;
+    let channel_id = handler_lock.lock().await.current_channel()?;
+    Some(ChannelId::new(channel_id.0.get()))
+}
+
+/// Checks that the caller shares the bot's voice channel, telling them if not.
+/// Passes when the bot isn't in a call, the command will say so itself.
+pub(super) async fn require_same_channel(ctx: Context<'_>) -> bool {
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+    let Some(bot_channel) = bot_channel(&manager, ctx.guild_id().unwrap()).await else {
+        return true;
+    };
+    let listener = Listener::from_ctx(ctx).await;
+    if listener.is_some_and(|listener| listener.channel_id == Some(bot_channel)) {
+        return true;
+    }
+    check_msg(
+        ctx.say("You need to be in my voice channel to do that.")
+            .await,
+    );
+    false
+}
+
+/// Whether someone may wipe the queue: DJs, or anyone whose tracks are all
+/// that's left.
+pub(super) async fn may_clear(listener: &Listener, manager: &Songbird, guild_id: GuildId) -> bool {
+    if listener.is_dj {
+        return true;
+    }
+    let Some(handler_lock) = manager.get(guild_id) else {
+        return true;
+    };
+    let tracks = handler_lock.lock().await.queue().current_queue();
+    for track in &tracks {
+        if get_metadata(track).await.requester != Some(listener.user_id) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Skips the current track for DJs and whoever requested it, and counts a
+/// vote from anyone else. Returns what to tell the listener.
+pub(super) async fn skip(
+    cache: &Cache,
+    manager: &Songbird,
+    config: Option<&Guild>,
+    guild_id: GuildId,
+    listener: &Listener,
+) -> String {
+    let Some(handler_lock) = manager.get(guild_id) else {
+        return "Not in a voice channel.".to_string();
+    };
+    let Some(track) = handler_lock.lock().await.queue().current() else {
+        return "Nothing is playing.".to_string();
+    };
+
+    let requester = get_metadata(&track).await.requester;
+    if !listener.is_dj && requester != Some(listener.user_id) {
+        let listeners = match bot_channel(manager, guild_id).await {
+            Some(channel_id) => listeners(cache, guild_id, channel_id),
+            None => HashSet::new(),
+        };
+        let percent = config.map_or(DEFAULT_VOTE_SKIP_PERCENT, |c| c.vote_skip_percent);
+        let needed = (listeners.len() * percent as usize).div_ceil(100).max(1);
+
+        let session = session::get(guild_id).await;
+        let mut session = session.lock().await;
+        session.skip_votes.insert(listener.user_id);
+        // People who voted and then left don't count anymore.
+        let votes = session.skip_votes.intersection(&listeners).count();
+        if votes < needed {
+            return format!("Voted to skip ({}/{}).", votes, needed);
+        }
+    }
+
+    let skipped = handler_lock.lock().await.queue().skip();
+    match skipped {
+        Ok(()) => "Skipped audio track.".to_string(),
+        Err(why) => {
+            error!("Err skip source {:?}", why);
+            "Couldn't skip this track.".to_string()
+        }
+    }
+}
+
+/// Members in a voice channel who aren't bots.
+fn listeners(cache: &Cache, guild_id: GuildId, channel_id: ChannelId) -> HashSet<UserId> {
+    let Some(guild) = cache.guild(guild_id) else {
+        return HashSet::new();
+    };
+    guild
+        .voice_states
+        .values()
+        .filter(|state| state.channel_id == Some(channel_id))
+        .filter(|state| match &state.member {
+            Some(member) => !member.user.bot,
+            None => !cache.user(state.user_id).is_some_and(|user| user.bot),
+        })
+        .map(|state| state.user_id)
+        .collect()
+}
+
+/// Checks [`may_clear`] for the caller, telling them if they can't.
+pub(super) async fn require_may_clear(ctx: Context<'_>, manager: &Songbird) -> bool {
+    let Some(listener) = Listener::from_ctx(ctx).await else {
+        return false;
+    };
+    if may_clear(&listener, manager, ctx.guild_id().unwrap()).await {
+        return true;
+    }
+    check_msg(
+        ctx.say("Only DJs can do that while others have tracks queued.")
+            .await,
+    );
+    false
+}
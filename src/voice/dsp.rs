@@ -0,0 +1,366 @@
+use std::f32::consts::PI;
+
+/// Playback speed of the nightcore preset, which raises pitch and tempo
+/// together.
+pub const NIGHTCORE_SPEED: f32 = 1.25;
+
+/// An effect applied to every track a guild plays.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Filter {
+    #[default]
+    Off,
+    BassBoost,
+    Nightcore,
+    /// Plays faster or slower by this factor, pitch included.
+    Speed(f32),
+    /// Slowly circles the sound around the listener.
+    EightD,
+    LowPass,
+}
+
+impl Filter {
+    /// How much faster than normal the filter plays audio.
+    pub fn speed(self) -> f32 {
+        match self {
+            Filter::Nightcore => NIGHTCORE_SPEED,
+            Filter::Speed(speed) => speed,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Processes one track's interleaved stereo samples, keeping the filter
+/// state between chunks so effects run on without clicks.
+pub struct FilterChain {
+    sample_rate: f32,
+    /// The filter the biquads were set up for.
+    filter: Filter,
+    biquads: [Biquad; 2],
+    resampler: Resampler,
+    /// Where the 8D rotation is, in radians.
+    pan_phase: f32,
+}
+
+/// How long the 8D effect takes to go around once.
+const PAN_PERIOD_SECS: f32 = 8.0;
+
+impl FilterChain {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate as f32,
+            filter: Filter::Off,
+            biquads: [Biquad::default(), Biquad::default()],
+            resampler: Resampler::default(),
+            pan_phase: 0.0,
+        }
+    }
+
+    /// Runs `input` through `filter`, appending the result to `out`. Both
+    /// are interleaved stereo, but `out` gets fewer or more frames when the
+    /// filter changes the speed.
+    pub fn process(&mut self, filter: Filter, input: &[f32], out: &mut Vec<f32>) {
+        if filter != self.filter {
+            self.switch(filter);
+        }
+
+        let start = out.len();
+        let speed = filter.speed();
+        if speed == 1.0 {
+            out.extend_from_slice(input);
+        } else {
+            self.resampler.process(speed, input, out);
+        }
+        let samples = &mut out[start..];
+
+        match filter {
+            Filter::BassBoost | Filter::LowPass => {
+                for frame in samples.chunks_exact_mut(2) {
+                    frame[0] = self.biquads[0].process(frame[0]);
+                    frame[1] = self.biquads[1].process(frame[1]);
+                }
+            }
+            Filter::EightD => {
+                let step = 2.0 * PI / (PAN_PERIOD_SECS * self.sample_rate);
+                for frame in samples.chunks_exact_mut(2) {
+                    // Equal-power panning of the mid signal, so the volume
+                    // stays the same as it goes around.
+                    let pan = self.pan_phase.sin();
+                    let mid = (frame[0] + frame[1]) / 2.0;
+                    frame[0] = mid * (1.0 - pan).sqrt();
+                    frame[1] = mid * (1.0 + pan).sqrt();
+                    self.pan_phase = (self.pan_phase + step) % (2.0 * PI);
+                }
+            }
+            Filter::Off | Filter::Nightcore | Filter::Speed(_) => {}
+        }
+
+        for sample in samples {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+
+    /// Forgets everything about the audio so far, after a seek.
+    pub fn reset(&mut self) {
+        for biquad in &mut self.biquads {
+            biquad.clear();
+        }
+        self.resampler = Resampler::default();
+    }
+
+    fn switch(&mut self, filter: Filter) {
+        let coefficients = match filter {
+            // A shelf boosting everything below ~110 Hz, lowered overall so
+            // loud bass doesn't clip.
+            Filter::BassBoost => Some(Coefficients::low_shelf(self.sample_rate, 110.0, 9.0, -3.0)),
+            Filter::LowPass => Some(Coefficients::low_pass(self.sample_rate, 600.0)),
+            _ => None,
+        };
+        if let Some(coefficients) = coefficients {
+            for biquad in &mut self.biquads {
+                biquad.coefficients = coefficients;
+            }
+        }
+        self.filter = filter;
+    }
+}
+
+/// Second-order IIR filter coefficients, normalised so `a0` is 1.
+#[derive(Debug, Clone, Copy)]
+struct Coefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Default for Coefficients {
+    fn default() -> Self {
+        Self {
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+        }
+    }
+}
+
+impl Coefficients {
+    /// Low shelf from the Audio EQ Cookbook, with a shelf slope of 1 and
+    /// `gain_db` of extra gain applied to everything.
+    fn low_shelf(sample_rate: f32, frequency: f32, boost_db: f32, gain_db: f32) -> Self {
+        let a = 10f32.powf(boost_db / 40.0);
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / 2.0 * 2f32.sqrt();
+        let sqrt_a = a.sqrt();
+
+        let a0 = (a + 1.0) + (a - 1.0) * cos + 2.0 * sqrt_a * alpha;
+        let gain = 10f32.powf(gain_db / 20.0) / a0;
+        Self {
+            b0: gain * a * ((a + 1.0) - (a - 1.0) * cos + 2.0 * sqrt_a * alpha),
+            b1: gain * 2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            b2: gain * a * ((a + 1.0) - (a - 1.0) * cos - 2.0 * sqrt_a * alpha),
+            a1: -2.0 * ((a - 1.0) + (a + 1.0) * cos) / a0,
+            a2: ((a + 1.0) + (a - 1.0) * cos - 2.0 * sqrt_a * alpha) / a0,
+        }
+    }
+
+    /// Butterworth low-pass from the Audio EQ Cookbook.
+    fn low_pass(sample_rate: f32, frequency: f32) -> Self {
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * std::f32::consts::FRAC_1_SQRT_2);
+
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: (1.0 - cos) / 2.0 / a0,
+            b1: (1.0 - cos) / a0,
+            b2: (1.0 - cos) / 2.0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+}
+
+/// One channel of a biquad filter, in transposed direct form II.
+#[derive(Debug, Clone, Default)]
+struct Biquad {
+    coefficients: Coefficients,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    fn process(&mut self, x: f32) -> f32 {
+        let c = &self.coefficients;
+        let y = c.b0 * x + self.z1;
+        self.z1 = c.b1 * x - c.a1 * y + self.z2;
+        self.z2 = c.b2 * x - c.a2 * y;
+        y
+    }
+
+    fn clear(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+/// Changes speed by linear interpolation between frames, carrying its place
+/// over from one chunk to the next.
+#[derive(Debug, Clone, Default)]
+struct Resampler {
+    /// The frame before the next input frame, `None` until the first one.
+    previous: Option<[f32; 2]>,
+    /// How far past `previous` the next output frame is, in frames.
+    position: f32,
+}
+
+impl Resampler {
+    fn process(&mut self, speed: f32, input: &[f32], out: &mut Vec<f32>) {
+        let mut frames = input.chunks_exact(2).map(|frame| [frame[0], frame[1]]);
+        let mut previous = match self.previous {
+            Some(previous) => previous,
+            None => match frames.next() {
+                Some(first) => first,
+                None => return,
+            },
+        };
+        let mut next = frames.next();
+
+        while let Some(frame) = next {
+            if self.position >= 1.0 {
+                self.position -= 1.0;
+                previous = frame;
+                next = frames.next();
+                continue;
+            }
+            for channel in 0..2 {
+                let sample =
+                    previous[channel] + (frame[channel] - previous[channel]) * self.position;
+                out.push(sample);
+            }
+            self.position += speed;
+        }
+        self.previous = Some(previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48_000;
+
+    /// One second of a stereo sine wave at `frequency`.
+    fn sine(frequency: f32, amplitude: f32) -> Vec<f32> {
+        (0..RATE)
+            .flat_map(|i| {
+                let sample = amplitude * (2.0 * PI * frequency * i as f32 / RATE as f32).sin();
+                [sample, sample]
+            })
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    /// Runs a whole buffer through a fresh chain, in 20ms chunks like songbird
+    /// would.
+    fn run(filter: Filter, input: &[f32]) -> Vec<f32> {
+        let mut chain = FilterChain::new(RATE);
+        let mut out = Vec::new();
+        for chunk in input.chunks(2 * RATE as usize / 50) {
+            chain.process(filter, chunk, &mut out);
+        }
+        out
+    }
+
+    /// The second half of a buffer, once filters have settled.
+    fn settled(samples: &[f32]) -> &[f32] {
+        &samples[samples.len() / 2..]
+    }
+
+    #[test]
+    fn off_passes_audio_through() {
+        let input = sine(440.0, 0.5);
+        assert_eq!(run(Filter::Off, &input), input);
+    }
+
+    #[test]
+    fn bass_boost_raises_lows_only() {
+        let low = sine(50.0, 0.2);
+        let high = sine(5_000.0, 0.2);
+        let low_gain = rms(settled(&run(Filter::BassBoost, &low))) / rms(&low);
+        let high_gain = rms(settled(&run(Filter::BassBoost, &high))) / rms(&high);
+        assert!(low_gain > 1.5, "low gain {}", low_gain);
+        assert!((0.6..0.8).contains(&high_gain), "high gain {}", high_gain);
+    }
+
+    #[test]
+    fn low_pass_cuts_highs() {
+        let low = sine(100.0, 0.5);
+        let high = sine(8_000.0, 0.5);
+        let low_gain = rms(settled(&run(Filter::LowPass, &low))) / rms(&low);
+        let high_gain = rms(settled(&run(Filter::LowPass, &high))) / rms(&high);
+        assert!(low_gain > 0.95, "low gain {}", low_gain);
+        assert!(high_gain < 0.01, "high gain {}", high_gain);
+    }
+
+    #[test]
+    fn speed_changes_length() {
+        let input = sine(440.0, 0.5);
+        let frames = input.len() / 2;
+        let nightcore = run(Filter::Nightcore, &input).len() / 2;
+        let double = run(Filter::Speed(2.0), &input).len() / 2;
+        let half = run(Filter::Speed(0.5), &input).len() / 2;
+        assert!(nightcore.abs_diff((frames as f32 / NIGHTCORE_SPEED) as usize) <= 1);
+        assert!(double.abs_diff(frames / 2) <= 1);
+        assert!(half.abs_diff(frames * 2) <= 2);
+    }
+
+    #[test]
+    fn speed_is_seamless_across_chunks() {
+        let input = sine(440.0, 0.5);
+        let mut whole = Vec::new();
+        FilterChain::new(RATE).process(Filter::Speed(1.5), &input, &mut whole);
+        let chunked = run(Filter::Speed(1.5), &input);
+        assert_eq!(whole.len(), chunked.len());
+        for (a, b) in whole.iter().zip(&chunked) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn eight_d_moves_between_ears() {
+        let input = sine(440.0, 0.5);
+        let mut chain = FilterChain::new(RATE);
+        let mut out = Vec::new();
+        // A quarter of the way round, the sound is fully on the right.
+        let quarter = (PAN_PERIOD_SECS / 4.0 * RATE as f32) as usize;
+        while out.len() < 2 * (quarter + RATE as usize / 10) {
+            chain.process(Filter::EightD, &input, &mut out);
+        }
+        let around_quarter = &out[2 * (quarter - 480)..2 * (quarter + 480)];
+        let left: Vec<_> = around_quarter.iter().step_by(2).copied().collect();
+        let right: Vec<_> = around_quarter.iter().skip(1).step_by(2).copied().collect();
+        assert!(rms(&left) < 0.02, "left {}", rms(&left));
+        assert!(rms(&right) > 0.4, "right {}", rms(&right));
+
+        // At the start it's centred.
+        let start = &out[..2 * 480];
+        let left: Vec<_> = start.iter().step_by(2).copied().collect();
+        let right: Vec<_> = start.iter().skip(1).step_by(2).copied().collect();
+        assert!((rms(&left) - rms(&right)).abs() < 0.05);
+    }
+
+    #[test]
+    fn output_never_clips() {
+        let input = sine(60.0, 1.0);
+        for filter in [Filter::BassBoost, Filter::EightD, Filter::Nightcore] {
+            assert!(run(filter, &input).iter().all(|s| s.abs() <= 1.0));
+        }
+    }
+}
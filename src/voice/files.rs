@@ -0,0 +1,333 @@
+use crate::voice::cmds::{check_msg, enqueue, get_http_client, join_vc, TrackInfo};
+use crate::voice::pages;
+use crate::voice::sources::{LocalFiles, Resolved, TrackSource};
+use crate::{Context, Error};
+use serenity::all::Attachment;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+use songbird::input::codecs::PROBE;
+use songbird::input::{AudioStream, AuxMetadata, Compose, HttpRequest};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSource, MediaSourceStream, MediaSourceStreamOptions};
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use tracing::error;
+
+/// URL prefix of tracks from the local music library, followed by their path
+/// inside it.
+pub const LIBRARY_PREFIX: &str = "file:";
+
+/// Extensions of the files `/voice library` offers to play.
+const AUDIO_EXTENSIONS: &[&str] = &[
+    "aac", "flac", "m4a", "mka", "mkv", "mp3", "mp4", "oga", "ogg", "opus", "wav", "webm",
+];
+
+const PAGE_SIZE: usize = 15;
+
+/// Play an audio file uploaded to Discord
+#[poise::command(slash_command, guild_only, rename = "play-file")]
+pub async fn play_file(
+    ctx: Context<'_>,
+    #[description = "Audio file to play"] file: Attachment,
+) -> Result<(), Error> {
+    let is_audio = file
+        .content_type
+        .as_deref()
+        .is_some_and(|kind| kind.starts_with("audio/") || kind.starts_with("video/"))
+        || is_audio_file(Path::new(&file.filename));
+    if !is_audio {
+        check_msg(ctx.say("That doesn't look like an audio file.").await);
+        return Ok(());
+    }
+    ctx.defer().await?;
+
+    let http_client = get_http_client(&ctx).await;
+    let mut request = HttpRequest::new(http_client.clone(), file.url.clone());
+    let metadata = match request.create_async().await {
+        Ok(stream) => read_metadata(stream).await,
+        Err(why) => {
+            error!("Err opening attachment {:?}", why);
+            AuxMetadata::default()
+        }
+    };
+    let info = track_info(metadata, file.url.clone(), &file.filename);
+    let source = Box::new(HttpRequest::new(http_client, info.url.clone()));
+    play(ctx, Resolved { info, source }).await
+}
+
+/// Browse the local music library, or play a file from it
+#[poise::command(slash_command, guild_only)]
+pub async fn library(
+    ctx: Context<'_>,
+    #[description = "Folder to browse or file to play"]
+    #[autocomplete = "autocomplete_library"]
+    path: Option<String>,
+) -> Result<(), Error> {
+    if library_dir().is_none() {
+        check_msg(ctx.say("There is no music library set up.").await);
+        return Ok(());
+    }
+    let path = path.unwrap_or_default();
+    let Some(full) = resolve(&path) else {
+        check_msg(
+            ctx.say(format!(
+                "There is nothing called `{}` in the library.",
+                path
+            ))
+            .await,
+        );
+        return Ok(());
+    };
+    let path = library_path(&full).unwrap_or_default();
+
+    if full.is_dir() {
+        return browse(ctx, &path, full).await;
+    }
+    ctx.defer().await?;
+
+    match LocalFiles.resolve(&path).await {
+        Ok(resolved) => play(ctx, resolved).await,
+        Err(why) => {
+            check_msg(ctx.say(why.to_string()).await);
+            Ok(())
+        }
+    }
+}
+
+async fn autocomplete_library(_: Context<'_>, partial: &str) -> Vec<String> {
+    let (folder, name) = match partial.rsplit_once('/') {
+        Some((folder, name)) => (folder, name),
+        None => ("", partial),
+    };
+    let Some(full) = resolve(folder) else {
+        return Vec::new();
+    };
+    let Some(folder) = library_path(&full) else {
+        return Vec::new();
+    };
+    let name = name.to_lowercase();
+    list_dir(full)
+        .await
+        .into_iter()
+        .filter(|entry| entry.name.to_lowercase().contains(&name))
+        .map(|entry| {
+            let path = match folder.as_str() {
+                "" => entry.name,
+                folder => format!("{}/{}", folder, entry.name),
+            };
+            if entry.is_dir {
+                path + "/"
+            } else {
+                path
+            }
+        })
+        // Longer choices are rejected by Discord.
+        .filter(|path| path.len() <= 100)
+        .take(25)
+        .collect()
+}
+
+/// Joins the caller's channel if needed and queues a file.
+async fn play(ctx: Context<'_>, resolved: Resolved) -> Result<(), Error> {
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+    let guild_id = ctx.guild_id().unwrap();
+
+    if manager.get(guild_id).is_none() {
+        if let Err(why) = join_vc(ctx, manager.clone()).await {
+            check_msg(ctx.reply(why).await);
+        }
+    }
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        enqueue(
+            ctx,
+            manager.clone(),
+            &handler_lock,
+            resolved.source,
+            resolved.info,
+        )
+        .await;
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+/// Lists a library folder, a page at a time.
+async fn browse(ctx: Context<'_>, path: &str, full: PathBuf) -> Result<(), Error> {
+    let lines: Vec<_> = list_dir(full)
+        .await
+        .into_iter()
+        .map(|entry| {
+            if entry.is_dir {
+                format!("📁 `{}/`", entry.name)
+            } else {
+                format!("🎵 {}", entry.name)
+            }
+        })
+        .collect();
+    if lines.is_empty() {
+        check_msg(ctx.say("This folder is empty.").await);
+        return Ok(());
+    }
+
+    let pages = lines.len().div_ceil(PAGE_SIZE);
+    pages::paginate(ctx, pages, |page| {
+        let lines: Vec<_> = lines
+            .iter()
+            .skip(page * PAGE_SIZE)
+            .take(PAGE_SIZE)
+            .cloned()
+            .collect();
+        let footer = CreateEmbedFooter::new(format!("rauly.rs | Page {}/{}", page + 1, pages));
+        CreateEmbed::new()
+            .colour(0xeb984e)
+            .title(format!("Music Library | /{}", path))
+            .description(lines.join("\n"))
+            .footer(footer)
+    })
+    .await
+}
+
+pub(super) fn library_dir() -> Option<PathBuf> {
+    env::var("LIBRARY_DIR").ok().map(PathBuf::from)
+}
+
+/// Finds a path inside the music library, refusing anything that leads out of
+/// it.
+pub fn resolve(path: &str) -> Option<PathBuf> {
+    let root = library_dir()?.canonicalize().ok()?;
+    let full = root.join(path.trim_matches('/')).canonicalize().ok()?;
+    full.starts_with(&root).then_some(full)
+}
+
+/// The path of a resolved file relative to the library, with `/` between
+/// folders.
+pub(super) fn library_path(full: &Path) -> Option<String> {
+    let root = library_dir()?.canonicalize().ok()?;
+    let relative = full.strip_prefix(root).ok()?;
+    let parts: Vec<_> = relative
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect();
+    Some(parts.join("/"))
+}
+
+pub(super) fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+struct Entry {
+    name: String,
+    is_dir: bool,
+}
+
+/// Folders and then audio files in a library folder, leaving out hidden ones.
+async fn list_dir(path: PathBuf) -> Vec<Entry> {
+    let listed = tokio::task::spawn_blocking(move || {
+        let mut entries: Vec<_> = std::fs::read_dir(path)?
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let is_dir = entry.path().is_dir();
+                let wanted = !name.starts_with('.') && (is_dir || is_audio_file(&entry.path()));
+                wanted.then_some(Entry { name, is_dir })
+            })
+            .collect();
+        entries.sort_by_key(|entry| (!entry.is_dir, entry.name.to_lowercase()));
+        Ok::<_, std::io::Error>(entries)
+    })
+    .await;
+    match listed {
+        Ok(Ok(entries)) => entries,
+        Ok(Err(why)) => {
+            error!("Err listing library folder {:?}", why);
+            Vec::new()
+        }
+        Err(why) => {
+            error!("Err listing library folder {:?}", why);
+            Vec::new()
+        }
+    }
+}
+
+/// Turns a file's tags into track info, named after the file if it has no
+/// title.
+pub(super) fn track_info(mut metadata: AuxMetadata, url: String, name: &str) -> TrackInfo {
+    metadata.source_url = Some(url);
+    if metadata.title.is_none() {
+        let stem = Path::new(name).file_stem().unwrap_or_default();
+        metadata.title = Some(stem.to_string_lossy().into_owned());
+    }
+    TrackInfo::from(metadata)
+}
+
+/// Reads a file's title, artist and length. Missing tags are left empty.
+pub(super) async fn read_metadata(stream: AudioStream<Box<dyn MediaSource>>) -> AuxMetadata {
+    match tokio::task::spawn_blocking(move || probe_metadata(stream)).await {
+        Ok(Ok(metadata)) => metadata,
+        Ok(Err(why)) => {
+            error!("Err reading tags {:?}", why);
+            AuxMetadata::default()
+        }
+        Err(why) => {
+            error!("Err reading tags {:?}", why);
+            AuxMetadata::default()
+        }
+    }
+}
+
+fn probe_metadata(
+    stream: AudioStream<Box<dyn MediaSource>>,
+) -> Result<AuxMetadata, SymphoniaError> {
+    let hint = stream.hint.unwrap_or_default();
+    let input = MediaSourceStream::new(stream.input, MediaSourceStreamOptions::default());
+    let mut probed = PROBE.format(
+        &hint,
+        input,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+
+    let mut metadata = AuxMetadata::default();
+    // Tags in front of the file (like ID3) come first, the container's own
+    // tags win if both have them.
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        apply_tags(&mut metadata, revision);
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        apply_tags(&mut metadata, revision);
+    }
+
+    if let Some(track) = probed.format.default_track() {
+        let params = &track.codec_params;
+        if let (Some(time_base), Some(frames)) = (params.time_base, params.n_frames) {
+            let time = time_base.calc_time(frames);
+            metadata.duration = Some(Duration::from_secs_f64(time.seconds as f64 + time.frac));
+        }
+        metadata.sample_rate = params.sample_rate;
+        metadata.channels = params.channels.map(|channels| channels.count() as u8);
+    }
+    Ok(metadata)
+}
+
+fn apply_tags(metadata: &mut AuxMetadata, revision: &MetadataRevision) {
+    for tag in revision.tags() {
+        let value = tag.value.to_string();
+        match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => metadata.title = Some(value),
+            Some(StandardTagKey::Artist) => metadata.artist = Some(value),
+            Some(StandardTagKey::Album) => metadata.album = Some(value),
+            Some(StandardTagKey::Date) => metadata.date = Some(value),
+            _ => {}
+        }
+    }
+}
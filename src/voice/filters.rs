@@ -0,0 +1,685 @@
+use crate::voice::cmds::check_msg;
+use crate::voice::dj;
+use crate::voice::dsp::{Filter, FilterChain};
+use crate::voice::session;
+use crate::{Context, Error};
+use poise::ChoiceParameter;
+use serenity::async_trait;
+use serenity::prelude::TypeMapKey;
+use songbird::input::codecs::{CODEC_REGISTRY, PROBE};
+use songbird::input::{AudioStream, AudioStreamError, AuxMetadata, Compose};
+use songbird::tracks::TrackHandle;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use symphonia::core::audio::{SampleBuffer, SignalSpec};
+use symphonia::core::codecs::{Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSource, MediaSourceStream, MediaSourceStreamOptions};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::TimeBase;
+use tracing::warn;
+
+/// Header songbird's raw PCM reader expects, followed by the sample rate and
+/// channel count.
+const RAW_MAGIC: &[u8; 8] = b"SbirdRaw";
+const RAW_HEADER_LEN: u64 = 16;
+/// Bytes in one frame of interleaved stereo `f32`.
+const FRAME_LEN: u64 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, ChoiceParameter)]
+pub enum FilterPreset {
+    Off,
+    #[name = "Bass boost"]
+    BassBoost,
+    Nightcore,
+    Speed,
+    #[name = "8D"]
+    EightD,
+    #[name = "Low-pass"]
+    LowPass,
+}
+
+/// The filter a guild's tracks play through. Every track holds a clone, so
+/// changing it applies to the current track right away.
+#[derive(Clone, Default)]
+pub struct FilterHandle(Arc<Mutex<Filter>>);
+
+impl FilterHandle {
+    pub fn get(&self) -> Filter {
+        *self.0.lock().expect("filter mutex poisoned")
+    }
+
+    pub fn set(&self, filter: Filter) {
+        *self.0.lock().expect("filter mutex poisoned") = filter;
+    }
+}
+
+/// The guild filter as one track sees it, along with how the track's output
+/// has lined up with its media so far.
+#[derive(Clone)]
+pub struct TrackFilter {
+    guild: FilterHandle,
+    timeline: Arc<Mutex<Timeline>>,
+}
+
+impl TrackFilter {
+    pub fn new(guild: FilterHandle) -> Self {
+        // Until the track plays, the filter it would start with is the best
+        // guess, which lets a track be seeked before it loads.
+        let speed = guild.get().speed() as f64;
+        Self {
+            guild,
+            timeline: Arc::new(Mutex::new(Timeline::starting_at(0.0, 0.0, speed))),
+        }
+    }
+
+    fn timeline(&self) -> MutexGuard<'_, Timeline> {
+        self.timeline.lock().expect("timeline mutex poisoned")
+    }
+}
+
+pub struct TrackFilterKey;
+impl TypeMapKey for TrackFilterKey {
+    type Value = TrackFilter;
+}
+
+/// Stretches of a track's output, each played at one speed, in seconds.
+/// Speed filters make the output run ahead of or behind the media, by
+/// different amounts as the filter changes.
+struct Timeline {
+    segments: Vec<Segment>,
+}
+
+struct Segment {
+    /// Where the stretch starts in the output.
+    output: f64,
+    /// Where it starts in the media.
+    media: f64,
+    speed: f64,
+}
+
+impl Timeline {
+    fn starting_at(output: f64, media: f64, speed: f64) -> Self {
+        Self {
+            segments: vec![Segment {
+                output,
+                media,
+                speed,
+            }],
+        }
+    }
+
+    fn media_at(&self, output: f64) -> f64 {
+        let segment = self
+            .segments
+            .iter()
+            .rev()
+            .find(|segment| segment.output <= output)
+            .unwrap_or(&self.segments[0]);
+        segment.media + (output - segment.output) * segment.speed
+    }
+
+    fn output_at(&self, media: f64) -> f64 {
+        let segment = self
+            .segments
+            .iter()
+            .rev()
+            .find(|segment| segment.media <= media)
+            .unwrap_or(&self.segments[0]);
+        segment.output + (media - segment.media) / segment.speed
+    }
+
+    /// Notes that output from `output` on plays at `speed`.
+    fn set_speed(&mut self, output: f64, speed: f64) {
+        if self.segments.last().is_some_and(|last| last.speed == speed) {
+            return;
+        }
+        let media = self.media_at(output);
+        // A stretch that never got to play is replaced.
+        self.segments.retain(|segment| segment.output < output);
+        self.segments.push(Segment {
+            output,
+            media,
+            speed,
+        });
+    }
+}
+
+async fn convert(
+    track: &TrackHandle,
+    position: Duration,
+    to: fn(&Timeline, f64) -> f64,
+) -> Duration {
+    let typemap = track.typemap().read().await;
+    let Some(filter) = typemap.get::<TrackFilterKey>() else {
+        return position;
+    };
+    let converted = to(&filter.timeline(), position.as_secs_f64());
+    Duration::from_secs_f64(converted.max(0.0))
+}
+
+/// How far into its media a track is, given songbird's position for it.
+/// Songbird counts the audio it has played, which runs ahead of or behind the
+/// media under speed filters.
+pub async fn media_position(track: &TrackHandle, played: Duration) -> Duration {
+    convert(track, played, Timeline::media_at).await
+}
+
+/// The position songbird has to seek a track to for a point in its media.
+pub async fn playback_position(track: &TrackHandle, media: Duration) -> Duration {
+    convert(track, media, Timeline::output_at).await
+}
+
+/// Applies an audio filter to this and the following tracks
+#[poise::command(slash_command, guild_only)]
+pub async fn filter(
+    ctx: Context<'_>,
+    #[description = "Filter to apply, or off"] preset: FilterPreset,
+    #[description = "Playback speed for the speed filter, 1.5 by default"]
+    #[min = 0.5]
+    #[max = 2.0]
+    speed: Option<f32>,
+) -> Result<(), Error> {
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
+    let filter = match preset {
+        FilterPreset::Off => Filter::Off,
+        FilterPreset::BassBoost => Filter::BassBoost,
+        FilterPreset::Nightcore => Filter::Nightcore,
+        FilterPreset::Speed => Filter::Speed(speed.unwrap_or(1.5).clamp(0.5, 2.0)),
+        FilterPreset::EightD => Filter::EightD,
+        FilterPreset::LowPass => Filter::LowPass,
+    };
+    let guild_id = ctx.guild_id().unwrap();
+    session::get(guild_id).await.lock().await.filter.set(filter);
+
+    let reply = match filter {
+        Filter::Off => "Filters off.".to_string(),
+        Filter::Speed(speed) => format!("Playing at **{}x** speed.", speed),
+        _ => format!("Filter set to **{}**.", preset.name()),
+    };
+    check_msg(ctx.say(reply).await);
+    Ok(())
+}
+
+/// Wraps a track's source so its audio goes through the guild's filter before
+/// songbird mixes it.
+pub struct Filtered {
+    inner: Box<dyn Compose>,
+    filter: TrackFilter,
+}
+
+impl Filtered {
+    pub fn new(inner: Box<dyn Compose>, filter: TrackFilter) -> Self {
+        Self { inner, filter }
+    }
+
+    fn wrap(&self, stream: AudioStream<Box<dyn MediaSource>>) -> AudioStream<Box<dyn MediaSource>> {
+        let mut hint = Hint::new();
+        hint.with_extension("rawf32");
+        AudioStream {
+            input: Box::new(FilteredSource::new(stream, self.filter.clone())),
+            hint: Some(hint),
+        }
+    }
+}
+
+#[async_trait]
+impl Compose for Filtered {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let stream = self.inner.create()?;
+        Ok(self.wrap(stream))
+    }
+
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let stream = self.inner.create_async().await?;
+        Ok(self.wrap(stream))
+    }
+
+    fn should_create_async(&self) -> bool {
+        self.inner.should_create_async()
+    }
+
+    async fn aux_metadata(&mut self) -> Result<AuxMetadata, AudioStreamError> {
+        self.inner.aux_metadata().await
+    }
+}
+
+/// Decodes a stream itself and hands songbird the filtered audio as raw
+/// stereo PCM.
+///
+/// Decoding only starts on the first read, which songbird does on its own
+/// blocking threads.
+struct FilteredSource {
+    state: SourceState,
+    filter: TrackFilter,
+    seekable: bool,
+    /// Filtered audio waiting to be read.
+    pending: Vec<u8>,
+    /// How much of `pending` was read already.
+    offset: usize,
+    /// Position in the output, in bytes, header included.
+    position: u64,
+}
+
+enum SourceState {
+    Unopened(Option<AudioStream<Box<dyn MediaSource>>>),
+    Decoding(Box<Decoding>),
+}
+
+struct Decoding {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    sample_rate: u32,
+    time_base: Option<TimeBase>,
+    chain: FilterChain,
+    samples: Option<(SignalSpec, SampleBuffer<f32>)>,
+    /// Source frames to drop after an inexact seek.
+    skip_frames: u64,
+}
+
+impl FilteredSource {
+    fn new(stream: AudioStream<Box<dyn MediaSource>>, filter: TrackFilter) -> Self {
+        Self {
+            seekable: stream.input.is_seekable(),
+            state: SourceState::Unopened(Some(stream)),
+            filter,
+            pending: Vec::new(),
+            offset: 0,
+            position: 0,
+        }
+    }
+
+    fn open(stream: AudioStream<Box<dyn MediaSource>>) -> Result<Decoding, SymphoniaError> {
+        let hint = stream.hint.unwrap_or_default();
+        let input = MediaSourceStream::new(stream.input, MediaSourceStreamOptions::default());
+        let probed = PROBE.format(
+            &hint,
+            input,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?;
+        let format = probed.format;
+        let track = format
+            .default_track()
+            .or_else(|| format.tracks().first())
+            .ok_or(SymphoniaError::Unsupported("no audio track"))?;
+        let decoder = CODEC_REGISTRY.make(&track.codec_params, &DecoderOptions::default())?;
+        let track_id = track.id;
+        let sample_rate = track.codec_params.sample_rate.unwrap_or(48_000);
+        let time_base = track.codec_params.time_base;
+        Ok(Decoding {
+            format,
+            decoder,
+            track_id,
+            sample_rate,
+            time_base,
+            chain: FilterChain::new(sample_rate),
+            samples: None,
+            skip_frames: 0,
+        })
+    }
+
+    /// Makes sure the stream is open, writing the raw PCM header the first
+    /// time.
+    fn decoding(&mut self) -> io::Result<&mut Decoding> {
+        if let SourceState::Unopened(stream) = &mut self.state {
+            let stream = stream
+                .take()
+                .ok_or_else(|| io::Error::other("stream failed to open"))?;
+            let decoding = Self::open(stream).map_err(io::Error::other)?;
+            if self.position == 0 {
+                self.pending.extend_from_slice(RAW_MAGIC);
+                self.pending
+                    .extend_from_slice(&decoding.sample_rate.to_le_bytes());
+                self.pending.extend_from_slice(&2u32.to_le_bytes());
+                self.offset = 0;
+            }
+            self.state = SourceState::Decoding(Box::new(decoding));
+        }
+        match &mut self.state {
+            SourceState::Decoding(decoding) => Ok(decoding),
+            SourceState::Unopened(_) => unreachable!("stream was just opened"),
+        }
+    }
+
+    /// How much output was read so far, in seconds.
+    fn output_seconds(&mut self) -> io::Result<f64> {
+        let frames = self.position.saturating_sub(RAW_HEADER_LEN) / FRAME_LEN;
+        let sample_rate = self.decoding()?.sample_rate;
+        Ok(frames as f64 / sample_rate as f64)
+    }
+
+    /// Decodes and filters the next packet into `pending`. Returns `false`
+    /// once the stream is over.
+    fn fill(&mut self) -> io::Result<bool> {
+        let filter = self.filter.guild.get();
+        let output = self.output_seconds()?;
+        self.filter
+            .timeline()
+            .set_speed(output, filter.speed() as f64);
+        let decoding = self.decoding()?;
+        let mut stereo = Vec::new();
+        loop {
+            let packet = match decoding.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(why)) if why.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Ok(false);
+                }
+                Err(why) => return Err(io::Error::other(why)),
+            };
+            if packet.track_id() != decoding.track_id {
+                continue;
+            }
+            let decoded = match decoding.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(SymphoniaError::DecodeError(why)) => {
+                    warn!("Skipping undecodable packet: {}", why);
+                    continue;
+                }
+                Err(why) => return Err(io::Error::other(why)),
+            };
+
+            let spec = *decoded.spec();
+            let needed = decoded.capacity() * spec.channels.count();
+            let samples = match &mut decoding.samples {
+                Some((old_spec, samples)) if *old_spec == spec && samples.capacity() >= needed => {
+                    samples
+                }
+                samples => {
+                    let buffer = SampleBuffer::new(decoded.capacity() as u64, spec);
+                    &mut samples.insert((spec, buffer)).1
+                }
+            };
+            samples.copy_interleaved_ref(decoded);
+
+            let channels = spec.channels.count().max(1);
+            let skip = (decoding.skip_frames as usize).min(samples.samples().len() / channels);
+            decoding.skip_frames -= skip as u64;
+            for frame in samples.samples().chunks_exact(channels).skip(skip) {
+                let left = frame[0];
+                let right = frame.get(1).copied().unwrap_or(left);
+                stereo.extend_from_slice(&[left, right]);
+            }
+            if !stereo.is_empty() {
+                break;
+            }
+        }
+
+        let mut out = Vec::with_capacity(stereo.len());
+        decoding.chain.process(filter, &stereo, &mut out);
+        self.pending.clear();
+        self.offset = 0;
+        self.pending
+            .extend(out.iter().flat_map(|sample| sample.to_le_bytes()));
+        Ok(true)
+    }
+}
+
+impl Read for FilteredSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == 0 {
+            self.decoding()?;
+        }
+        while self.offset >= self.pending.len() {
+            if !self.fill()? {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.pending.len() - self.offset);
+        buf[..n].copy_from_slice(&self.pending[self.offset..self.offset + n]);
+        self.offset += n;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for FilteredSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(target) => target,
+            SeekFrom::Current(delta) => self
+                .position
+                .checked_add_signed(delta)
+                .ok_or(io::ErrorKind::InvalidInput)?,
+            SeekFrom::End(_) => return Err(io::ErrorKind::Unsupported.into()),
+        };
+        if target == self.position {
+            return Ok(target);
+        }
+        if target < RAW_HEADER_LEN {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+
+        // The output runs at the filter's speed, so the timeline maps it back
+        // onto the source.
+        let frame = (target - RAW_HEADER_LEN) / FRAME_LEN;
+        let output = frame as f64 / self.decoding()?.sample_rate as f64;
+        let seconds = self.filter.timeline().media_at(output);
+        let decoding = self.decoding()?;
+        let seeked = decoding
+            .format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: seconds.into(),
+                    track_id: Some(decoding.track_id),
+                },
+            )
+            .map_err(io::Error::other)?;
+        decoding.decoder.reset();
+        decoding.chain.reset();
+        // Accurate seeks may land a little early, the rest is skipped while
+        // decoding.
+        let early = seeked.required_ts.saturating_sub(seeked.actual_ts);
+        decoding.skip_frames = match decoding.time_base {
+            Some(time_base) => {
+                let time = time_base.calc_time(early);
+                ((time.seconds as f64 + time.frac) * decoding.sample_rate as f64) as u64
+            }
+            None => early,
+        };
+        let speed = self.filter.guild.get().speed();
+        *self.filter.timeline() = Timeline::starting_at(output, seconds, speed as f64);
+
+        self.pending.clear();
+        self.offset = 0;
+        self.position = target;
+        Ok(target)
+    }
+}
+
+impl MediaSource for FilteredSource {
+    fn is_seekable(&self) -> bool {
+        self.seekable
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voice::cmds::enqueue_info;
+    use crate::voice::sources::{wav, MemorySource, TrackSource};
+    use serenity::model::prelude::*;
+    use songbird::input::LiveInput;
+    use songbird::Call;
+    use std::io::Cursor;
+
+    const RATE: u32 = 48_000;
+
+    fn source(filter: Filter) -> FilteredSource {
+        let handle = FilterHandle::default();
+        handle.set(filter);
+        source_with(handle)
+    }
+
+    fn source_with(handle: FilterHandle) -> FilteredSource {
+        let stream = AudioStream {
+            input: Box::new(Cursor::new(wav(Duration::from_secs(1)))) as Box<dyn MediaSource>,
+            hint: None,
+        };
+        FilteredSource::new(stream, TrackFilter::new(handle))
+    }
+
+    /// Reads `secs` of output, or what's left of it.
+    fn read_secs(source: &mut FilteredSource, secs: f64) -> u64 {
+        let mut bytes = vec![0; (secs * RATE as f64) as usize * FRAME_LEN as usize];
+        let mut read = 0;
+        while read < bytes.len() {
+            match source.read(&mut bytes[read..]).unwrap() {
+                0 => break,
+                n => read += n,
+            }
+        }
+        read as u64 / FRAME_LEN
+    }
+
+    fn frames(bytes: &[u8]) -> u64 {
+        (bytes.len() as u64 - RAW_HEADER_LEN) / FRAME_LEN
+    }
+
+    #[test]
+    fn writes_raw_header_and_all_frames() {
+        let mut bytes = Vec::new();
+        source(Filter::Off).read_to_end(&mut bytes).unwrap();
+        assert_eq!(&bytes[..8], RAW_MAGIC);
+        assert_eq!(&bytes[8..12], &RATE.to_le_bytes());
+        assert_eq!(&bytes[12..16], &2u32.to_le_bytes());
+        assert_eq!(frames(&bytes), RATE as u64);
+    }
+
+    #[test]
+    fn speed_shortens_output() {
+        let mut bytes = Vec::new();
+        source(Filter::Speed(2.0)).read_to_end(&mut bytes).unwrap();
+        assert!(frames(&bytes).abs_diff(RATE as u64 / 2) <= 1);
+    }
+
+    #[test]
+    fn seeks_to_the_right_frame() {
+        let mut source = source(Filter::Off);
+        let halfway = RAW_HEADER_LEN + FRAME_LEN * RATE as u64 / 2;
+        assert_eq!(source.seek(SeekFrom::Start(halfway)).unwrap(), halfway);
+        let mut rest = Vec::new();
+        source.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest.len() as u64 / FRAME_LEN, RATE as u64 / 2);
+    }
+
+    #[test]
+    fn filters_start_mid_track() {
+        let handle = FilterHandle::default();
+        let mut source = source_with(handle.clone());
+        read_secs(
+            &mut source,
+            RAW_HEADER_LEN as f64 / (FRAME_LEN * RATE as u64) as f64,
+        );
+        assert_eq!(read_secs(&mut source, 0.5), RATE as u64 / 2);
+
+        // The filter comes in with the next packet decoded, and the rest of
+        // the media plays twice as fast.
+        handle.set(Filter::Speed(2.0));
+        let rest = read_secs(&mut source, 1.0);
+        let timeline = source.filter.timeline();
+        let switched = timeline.output_at(1.0) - 0.5;
+        assert!(switched > 0.25 && switched < 0.26);
+        assert!(rest.abs_diff((switched * RATE as f64) as u64) <= 1);
+    }
+
+    #[test]
+    fn seeks_by_the_speeds_played_at() {
+        let handle = FilterHandle::default();
+        let mut source = source_with(handle.clone());
+        read_secs(
+            &mut source,
+            RAW_HEADER_LEN as f64 / (FRAME_LEN * RATE as u64) as f64,
+        );
+        read_secs(&mut source, 0.25);
+        handle.set(Filter::Speed(2.0));
+        read_secs(&mut source, 0.25);
+
+        // Seeking back to 0.375 in the output lands past 0.375 in the
+        // media, as part of what was played ran at double speed.
+        let media = source.filter.timeline().media_at(0.375);
+        assert!(media > 0.375 && media < 0.5);
+        let output = RAW_HEADER_LEN + FRAME_LEN * (RATE as u64 * 3 / 8);
+        source.seek(SeekFrom::Start(output)).unwrap();
+        let rest = read_secs(&mut source, 1.0);
+        let expected = (1.0 - media) / 2.0 * RATE as f64;
+        assert!(rest.abs_diff(expected as u64) <= 1);
+    }
+
+    #[test]
+    fn timeline_follows_speed_changes() {
+        let mut timeline = Timeline::starting_at(0.0, 0.0, 1.0);
+        timeline.set_speed(10.0, 2.0);
+        timeline.set_speed(20.0, 0.5);
+        assert_eq!(timeline.media_at(5.0), 5.0);
+        assert_eq!(timeline.media_at(15.0), 20.0);
+        assert_eq!(timeline.media_at(24.0), 32.0);
+        assert_eq!(timeline.output_at(20.0), 15.0);
+        assert_eq!(timeline.output_at(32.0), 24.0);
+    }
+
+    #[tokio::test]
+    async fn positions_follow_the_media() {
+        let guild_id = GuildId::new(20_001);
+        let mut call = Call::standalone(guild_id, UserId::new(1));
+        session::get(guild_id)
+            .await
+            .lock()
+            .await
+            .filter
+            .set(Filter::Speed(1.5));
+        let resolved = MemorySource::default()
+            .with_track("Intro", 90)
+            .resolve("Intro")
+            .await
+            .unwrap();
+        let track = enqueue_info(&mut call, guild_id, resolved.source, resolved.info, 1.0).await;
+
+        let played = Duration::from_secs(20);
+        assert_eq!(
+            media_position(&track, played).await,
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            playback_position(&track, Duration::from_secs(30)).await,
+            played
+        );
+
+        // Back to normal speed 10 seconds in, 15 seconds into the media.
+        if let Some(filter) = track.typemap().read().await.get::<TrackFilterKey>() {
+            filter.timeline().set_speed(10.0, 1.0);
+        }
+        assert_eq!(
+            media_position(&track, played).await,
+            Duration::from_secs(25)
+        );
+        assert_eq!(
+            playback_position(&track, Duration::from_secs(25)).await,
+            played
+        );
+        call.queue().stop();
+    }
+
+    #[test]
+    fn songbird_reads_the_output() {
+        let stream = AudioStream {
+            input: Box::new(source(Filter::BassBoost)) as Box<dyn MediaSource>,
+            hint: None,
+        };
+        let live = LiveInput::Raw(stream).promote(&CODEC_REGISTRY, &PROBE);
+        assert!(matches!(live, Ok(LiveInput::Parsed(_))));
+    }
+}
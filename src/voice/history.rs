@@ -0,0 +1,188 @@
+use crate::models::queue::PlayedTrack;
+use crate::voice::cmds::{
+    check_msg, enqueue, enqueue_info, format_duration, get_http_client, get_metadata, guild_volume,
+    join_vc, song_embed, source_for, title_link, TrackInfo,
+};
+use crate::voice::dj::{self, Listener};
+use crate::voice::{controls, pages, persist};
+use crate::{Context, Error};
+use chrono::Utc;
+use serenity::all::Mentionable;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+use tracing::error;
+
+const PAGE_SIZE: usize = 10;
+
+/// Shows the tracks that played recently
+#[poise::command(slash_command, guild_only)]
+pub async fn history(ctx: Context<'_>) -> Result<(), Error> {
+    let played = ctx.data().db.history(ctx.guild_id().unwrap())?;
+    if played.is_empty() {
+        check_msg(ctx.say("Nothing has been played yet.").await);
+        return Ok(());
+    }
+
+    let pages = played.len().div_ceil(PAGE_SIZE);
+    pages::paginate(ctx, pages, |page| history_page(&played, page)).await
+}
+
+fn history_page(played: &[PlayedTrack], page: usize) -> CreateEmbed {
+    let pages = played.len().div_ceil(PAGE_SIZE);
+    let lines: Vec<_> = played
+        .iter()
+        .enumerate()
+        .skip(page * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .map(|(i, played)| {
+            let track = &played.track;
+            let duration = track.duration.map_or("?".to_string(), format_duration);
+            let mut line = format!(
+                "**{}**. {} `{}` <t:{}:R>",
+                i + 1,
+                title_link(&track.title, &track.url),
+                duration,
+                played.played_at.timestamp()
+            );
+            if let Some(requester) = track.requester {
+                line += &format!(" · {}", requester.mention());
+            }
+            line
+        })
+        .collect();
+
+    let footer = CreateEmbedFooter::new(format!("rauly.rs | Page {}/{}", page + 1, pages));
+    CreateEmbed::new()
+        .colour(0xeb984e)
+        .title("Recently Played")
+        .description(lines.join("\n"))
+        .footer(footer)
+}
+
+/// Queues a track from the history again
+#[poise::command(slash_command, guild_only)]
+pub async fn replay(
+    ctx: Context<'_>,
+    #[description = "Number of the track, as in /voice history"]
+    #[min = 1]
+    number: usize,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(played) = ctx.data().db.history(guild_id)?.into_iter().nth(number - 1) else {
+        check_msg(ctx.say(format!("No track at number {}.", number)).await);
+        return Ok(());
+    };
+    ctx.defer().await?;
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if manager.get(guild_id).is_none() {
+        if let Err(why) = join_vc(ctx, manager.clone()).await {
+            check_msg(ctx.reply(why).await);
+        }
+    }
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let http_client = get_http_client(&ctx).await;
+        let info = TrackInfo::from(played.track);
+        match source_for(http_client, &info) {
+            Ok(source) => enqueue(ctx, manager.clone(), &handler_lock, source, info).await,
+            Err(why) => check_msg(ctx.say(why.to_string()).await),
+        }
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+/// Plays the last finished track again
+#[poise::command(slash_command, guild_only)]
+pub async fn previous(ctx: Context<'_>) -> Result<(), Error> {
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(played) = ctx.data().db.history(guild_id)?.into_iter().next() else {
+        check_msg(ctx.say("Nothing has been played yet.").await);
+        return Ok(());
+    };
+    ctx.defer().await?;
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if manager.get(guild_id).is_none() {
+        if let Err(why) = join_vc(ctx, manager.clone()).await {
+            check_msg(ctx.reply(why).await);
+        }
+    }
+
+    let Some(handler_lock) = manager.get(guild_id) else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+        return Ok(());
+    };
+    let listener = Listener::from_ctx(ctx).await;
+    let http_client = get_http_client(&ctx).await;
+    let volume = guild_volume(ctx, guild_id).await;
+    let info = TrackInfo {
+        requester: Some(ctx.author().id),
+        enqueued_at: Utc::now(),
+        ..TrackInfo::from(played.track)
+    };
+    let source = match source_for(http_client, &info) {
+        Ok(source) => source,
+        Err(why) => {
+            check_msg(ctx.say(why.to_string()).await);
+            return Ok(());
+        }
+    };
+
+    let mut handler = handler_lock.lock().await;
+    let current = handler.queue().current();
+    enqueue_info(&mut handler, guild_id, source, info.clone(), volume).await;
+    // Goes right after the current track instead of to the back.
+    handler.queue().modify_queue(|queue| {
+        if queue.len() > 2 {
+            if let Some(track) = queue.pop_back() {
+                queue.insert(1, track);
+            }
+        }
+    });
+
+    // Whoever may skip the current track gets the previous one right away,
+    // anyone else has it play next.
+    let position = match current {
+        Some(current) => {
+            let may_skip = match &listener {
+                Some(listener) => {
+                    listener.is_dj
+                        || get_metadata(&current).await.requester == Some(listener.user_id)
+                }
+                None => false,
+            };
+            match may_skip.then(|| handler.queue().skip()) {
+                Some(Ok(())) => 0,
+                Some(Err(why)) => {
+                    error!("Err skip source {:?}", why);
+                    1
+                }
+                None => 1,
+            }
+        }
+        None => 0,
+    };
+    drop(handler);
+
+    check_msg(
+        ctx.send(poise::CreateReply::default().embed(song_embed(&info, position)))
+            .await,
+    );
+    persist::save(&ctx.data().db, &manager, guild_id).await;
+    controls::show(ctx, manager).await;
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use crate::voice::lyrics::{parse_lrc, plain_from_lrc, Lyrics, LyricsProvider, LyricsQuery};
+use anyhow::Result;
+use serenity::async_trait;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `.lrc` and `.txt` files in a folder, named like `Artist - Title.lrc` or
+/// just `Title.txt`. Timed `.lrc` files win over plain ones.
+pub struct LocalLyrics {
+    dir: PathBuf,
+}
+
+impl LocalLyrics {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl LyricsProvider for LocalLyrics {
+    fn name(&self) -> &'static str {
+        "local files"
+    }
+
+    async fn find(&self, query: &LyricsQuery) -> Result<Option<Lyrics>> {
+        let dir = self.dir.clone();
+        let query = query.clone();
+        tokio::task::spawn_blocking(move || find_file(&dir, &query)).await?
+    }
+}
+
+fn find_file(dir: &Path, query: &LyricsQuery) -> Result<Option<Lyrics>> {
+    let wanted: Vec<_> = query
+        .artist
+        .iter()
+        .map(|artist| normalize(&format!("{} {}", artist, query.title)))
+        .chain([normalize(&query.title)])
+        .collect();
+
+    let mut best: Option<((usize, bool), PathBuf)> = None;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        let is_lrc = match extension.as_deref() {
+            Some("lrc") => true,
+            Some("txt") => false,
+            _ => continue,
+        };
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let Some(rank) = wanted.iter().position(|name| *name == normalize(&stem)) else {
+            continue;
+        };
+        let key = (rank, !is_lrc);
+        if best.as_ref().is_none_or(|(best, _)| key < *best) {
+            best = Some((key, path));
+        }
+    }
+    let Some(((_, is_txt), path)) = best else {
+        return Ok(None);
+    };
+
+    let content = fs::read_to_string(&path)?;
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let (artist, title) = match stem.split_once(" - ") {
+        Some((artist, title)) => (Some(artist.trim().to_string()), title.trim().to_string()),
+        None => (None, stem.trim().to_string()),
+    };
+    let (plain, synced) = if is_txt {
+        (content, Vec::new())
+    } else {
+        let synced = parse_lrc(&content);
+        (plain_from_lrc(&synced), synced)
+    };
+    Ok(Some(Lyrics {
+        title,
+        artist,
+        plain,
+        synced,
+    }))
+}
+
+/// Lowercase words without punctuation, so `AC/DC - T.N.T.` matches `acdc tnt`.
+fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// A fresh folder of lyrics files for one test.
+    fn lyrics_dir(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("raulyrs-lyrics-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (name, content) in files {
+            fs::write(dir.join(name), content).unwrap();
+        }
+        dir
+    }
+
+    fn query(title: &str, artist: Option<&str>) -> LyricsQuery {
+        LyricsQuery {
+            title: title.to_string(),
+            artist: artist.map(str::to_string),
+            duration: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_timed_lyrics_by_artist_and_title() {
+        let dir = lyrics_dir(
+            "timed",
+            &[
+                ("AC-DC - T.N.T..txt", "plain"),
+                ("AC-DC - T.N.T..lrc", "[00:01.00]Oi\n[00:02.50]Oi"),
+                ("Other.txt", "other"),
+            ],
+        );
+        let lyrics = LocalLyrics::new(&dir)
+            .find(&query("TNT", Some("ACDC")))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lyrics.artist.as_deref(), Some("AC-DC"));
+        assert_eq!(lyrics.plain, "Oi\nOi");
+        assert_eq!(lyrics.synced[1].at, Duration::from_millis(2_500));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_title() {
+        let dir = lyrics_dir("title", &[("Yesterday.txt", "All my troubles")]);
+        let lyrics = LocalLyrics::new(&dir)
+            .find(&query("Yesterday", Some("Uploader Name")))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lyrics.title, "Yesterday");
+        assert_eq!(lyrics.artist, None);
+        assert_eq!(lyrics.plain, "All my troubles");
+        assert!(lyrics.synced.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unknown_songs_have_no_lyrics() {
+        let dir = lyrics_dir("unknown", &[("Yesterday.txt", "All my troubles")]);
+        let found = LocalLyrics::new(&dir)
+            .find(&query("Tomorrow", None))
+            .await
+            .unwrap();
+        assert!(found.is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
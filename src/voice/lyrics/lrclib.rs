@@ -0,0 +1,97 @@
+use crate::voice::lyrics::{parse_lrc, plain_from_lrc, Lyrics, LyricsProvider, LyricsQuery};
+use anyhow::Result;
+use reqwest::header::USER_AGENT;
+use reqwest::Client as HttpClient;
+use serde::Deserialize;
+use serenity::async_trait;
+
+const SEARCH_URL: &str = "https://lrclib.net/api/search";
+
+/// [LRCLIB](https://lrclib.net), a free lyrics database with timed lyrics for
+/// many songs.
+pub struct LrcLib {
+    http_client: HttpClient,
+}
+
+impl LrcLib {
+    pub fn new(http_client: HttpClient) -> Self {
+        Self { http_client }
+    }
+
+    async fn search(&self, params: &[(&str, &str)]) -> Result<Vec<Record>> {
+        Ok(self
+            .http_client
+            .get(SEARCH_URL)
+            .header(USER_AGENT, concat!("raulyrs/", env!("CARGO_PKG_VERSION")))
+            .query(params)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Record {
+    track_name: String,
+    artist_name: String,
+    duration: Option<f64>,
+    #[serde(default)]
+    instrumental: bool,
+    plain_lyrics: Option<String>,
+    synced_lyrics: Option<String>,
+}
+
+impl Record {
+    fn into_lyrics(self) -> Option<Lyrics> {
+        let synced = parse_lrc(self.synced_lyrics.as_deref().unwrap_or_default());
+        let plain = match self.plain_lyrics.filter(|plain| !plain.trim().is_empty()) {
+            Some(plain) => plain,
+            None if !synced.is_empty() => plain_from_lrc(&synced),
+            None if self.instrumental => "🎵 Instrumental".to_string(),
+            None => return None,
+        };
+        Some(Lyrics {
+            title: self.track_name,
+            artist: Some(self.artist_name),
+            plain,
+            synced,
+        })
+    }
+}
+
+#[async_trait]
+impl LyricsProvider for LrcLib {
+    fn name(&self) -> &'static str {
+        "LRCLIB"
+    }
+
+    async fn find(&self, query: &LyricsQuery) -> Result<Option<Lyrics>> {
+        let mut records = match &query.artist {
+            Some(artist) => {
+                self.search(&[("track_name", &query.title), ("artist_name", artist)])
+                    .await?
+            }
+            None => Vec::new(),
+        };
+        // The artist may be a guess from the uploader, so try the title alone.
+        if records.is_empty() {
+            records = self.search(&[("q", &query.title)]).await?;
+        }
+
+        // Prefer the recording closest in length to the playing track.
+        let best = records
+            .into_iter()
+            .filter_map(|record| {
+                let off_by = match (query.duration, record.duration) {
+                    (Some(wanted), Some(secs)) => (wanted.as_secs_f64() - secs).abs() as u64,
+                    _ => 0,
+                };
+                record.into_lyrics().map(|lyrics| (off_by, lyrics))
+            })
+            .min_by_key(|(off_by, _)| *off_by);
+        Ok(best.map(|(_, lyrics)| lyrics))
+    }
+}
@@ -0,0 +1,484 @@
+mod local;
+mod lrclib;
+
+use crate::voice::cmds::{check_msg, get_http_client, get_metadata, TrackInfo};
+use crate::voice::{filters, pages};
+use crate::{Context, Error};
+use anyhow::Result;
+use reqwest::Client as HttpClient;
+use serenity::async_trait;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+use songbird::tracks::TrackHandle;
+use std::env;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tracing::error;
+
+pub(super) use local::LocalLyrics;
+pub(super) use lrclib::LrcLib;
+
+/// Longest embed description Discord accepts, in characters.
+const PAGE_LIMIT: usize = 4096;
+/// How often the synced view moves along with the track.
+const SYNC_INTERVAL: Duration = Duration::from_secs(3);
+/// Interaction replies can only be edited for 15 minutes, so the synced view
+/// stops following a little before that.
+const SYNC_LIMIT: Duration = Duration::from_secs(14 * 60);
+/// Lines shown around the current one in the synced view.
+const LINES_BEFORE: usize = 2;
+const LINES_AFTER: usize = 5;
+
+/// A song to find lyrics for.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct LyricsQuery {
+    pub title: String,
+    pub artist: Option<String>,
+    /// How long the playing track is, to tell versions of a song apart.
+    pub duration: Option<Duration>,
+}
+
+impl LyricsQuery {
+    /// What someone typed, split into artist and title if it looks like
+    /// `Artist - Title`.
+    fn from_text(text: &str) -> Self {
+        let text = strip_brackets(text);
+        match text.split_once(" - ") {
+            Some((artist, title)) => Self {
+                title: title.trim().to_string(),
+                artist: Some(artist.trim().to_string()),
+                duration: None,
+            },
+            None => Self {
+                title: text.trim().to_string(),
+                artist: None,
+                duration: None,
+            },
+        }
+    }
+
+    /// The playing track, or what's on air for radio. Video titles are often
+    /// `Artist - Song (Official Video)` from an uploader who isn't the
+    /// artist, so the title wins over the uploader.
+    fn from_track(info: &TrackInfo) -> Self {
+        if let Some(on_air) = &info.stream_title {
+            return Self::from_text(on_air);
+        }
+        let query = Self::from_text(&info.title);
+        let artist = query
+            .artist
+            .clone()
+            .or_else(|| info.artist.as_deref().map(clean_artist));
+        Self {
+            artist,
+            duration: info.duration,
+            ..query
+        }
+    }
+}
+
+impl fmt::Display for LyricsQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.artist {
+            Some(artist) => write!(f, "{} - {}", artist, self.title),
+            None => write!(f, "{}", self.title),
+        }
+    }
+}
+
+pub(super) struct Lyrics {
+    pub title: String,
+    pub artist: Option<String>,
+    pub plain: String,
+    /// Timed lines, empty if the provider only has plain lyrics.
+    pub synced: Vec<LrcLine>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct LrcLine {
+    pub at: Duration,
+    pub text: String,
+}
+
+/// Somewhere lyrics come from.
+#[async_trait]
+pub(super) trait LyricsProvider: Send + Sync {
+    /// Credited under the lyrics.
+    fn name(&self) -> &'static str;
+
+    /// The lyrics of a song, or `None` if the provider doesn't know it.
+    async fn find(&self, query: &LyricsQuery) -> Result<Option<Lyrics>>;
+}
+
+/// The provider set by `LYRICS_PROVIDER`: `lrclib` (the default), or `local`
+/// for `.lrc` and `.txt` files in `LYRICS_DIR`.
+fn provider(http_client: HttpClient) -> Box<dyn LyricsProvider> {
+    match env::var("LYRICS_PROVIDER").as_deref() {
+        Ok("local") => Box::new(LocalLyrics::new(env::var("LYRICS_DIR").unwrap_or_default())),
+        _ => Box::new(LrcLib::new(http_client)),
+    }
+}
+
+/// Shows the lyrics of the current track, or of any song
+#[poise::command(slash_command, guild_only)]
+pub async fn lyrics(
+    ctx: Context<'_>,
+    #[description = "Song to look up instead of the current track, like Artist - Title"]
+    query: Option<String>,
+    #[description = "Follow the current track line by line, if the lyrics are timed"]
+    synced: Option<bool>,
+) -> Result<(), Error> {
+    let current = current_track(ctx).await;
+    let lookup = match (&query, &current) {
+        (Some(query), _) => LyricsQuery::from_text(query),
+        (None, Some((_, info))) => LyricsQuery::from_track(info),
+        (None, None) => {
+            check_msg(ctx.say("Nothing is playing. Give a song to look up.").await);
+            return Ok(());
+        }
+    };
+    ctx.defer().await?;
+
+    let provider = provider(get_http_client(&ctx).await);
+    let lyrics = match provider.find(&lookup).await {
+        Ok(Some(lyrics)) => lyrics,
+        Ok(None) => {
+            check_msg(
+                ctx.say(format!("No lyrics found for **{}**.", lookup))
+                    .await,
+            );
+            return Ok(());
+        }
+        Err(why) => {
+            error!("Err fetching lyrics {:?}", why);
+            check_msg(ctx.say("Couldn't look up lyrics right now.").await);
+            return Ok(());
+        }
+    };
+
+    if synced.unwrap_or(false) {
+        match current {
+            Some((track, _)) if query.is_none() && !lyrics.synced.is_empty() => {
+                return follow(ctx, track, &lyrics, provider.name()).await;
+            }
+            _ => check_msg(
+                ctx.say("Only the current track's lyrics can be followed, when they're timed.")
+                    .await,
+            ),
+        }
+    }
+
+    let texts = split_pages(&lyrics.plain, PAGE_LIMIT);
+    pages::paginate(ctx, texts.len(), |page| {
+        let footer = format!(
+            "rauly.rs | Page {}/{} | Lyrics from {}",
+            page + 1,
+            texts.len(),
+            provider.name()
+        );
+        lyrics_embed(&lyrics, &texts[page], footer)
+    })
+    .await
+}
+
+async fn current_track(ctx: Context<'_>) -> Option<(TrackHandle, TrackInfo)> {
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+    let handler_lock = manager.get(ctx.guild_id()?)?;
+    let track = handler_lock.lock().await.queue().current()?;
+    let info = get_metadata(&track).await;
+    Some((track, info))
+}
+
+/// Shows the timed lyrics of the playing track, moving along with it until it
+/// ends.
+async fn follow(
+    ctx: Context<'_>,
+    track: TrackHandle,
+    lyrics: &Lyrics,
+    provider: &str,
+) -> Result<(), Error> {
+    let footer = format!("rauly.rs | Lyrics from {}", provider);
+    let started = Instant::now();
+    let mut handle = None;
+    let mut shown = None;
+    while started.elapsed() < SYNC_LIMIT {
+        let state = match track.get_info().await {
+            Ok(state) if !state.playing.is_done() => state,
+            _ => break,
+        };
+        let position = filters::media_position(&track, state.position).await;
+        let window = synced_window(&lyrics.synced, position);
+        if shown.as_ref() != Some(&window) {
+            let reply =
+                poise::CreateReply::default().embed(lyrics_embed(lyrics, &window, footer.clone()));
+            match &handle {
+                None => handle = Some(ctx.send(reply).await?),
+                Some(handle) => {
+                    if let Err(why) = handle.edit(ctx, reply).await {
+                        error!("Err updating lyrics {:?}", why);
+                        break;
+                    }
+                }
+            }
+            shown = Some(window);
+        }
+        tokio::time::sleep(SYNC_INTERVAL).await;
+    }
+
+    if handle.is_none() {
+        check_msg(ctx.say("The track is over.").await);
+    }
+    Ok(())
+}
+
+fn lyrics_embed(lyrics: &Lyrics, text: &str, footer: String) -> CreateEmbed {
+    let title = match &lyrics.artist {
+        Some(artist) => format!("Lyrics | {} - {}", artist, lyrics.title),
+        None => format!("Lyrics | {}", lyrics.title),
+    };
+    CreateEmbed::new()
+        .colour(0xeb984e)
+        .title(title)
+        .description(text)
+        .footer(CreateEmbedFooter::new(footer))
+}
+
+/// The lines around `position`, with the one being sung in bold.
+fn synced_window(lines: &[LrcLine], position: Duration) -> String {
+    let current = lines
+        .partition_point(|line| line.at <= position)
+        .checked_sub(1);
+    let start = current.map_or(0, |i| i.saturating_sub(LINES_BEFORE));
+    let end = (current.map_or(0, |i| i + 1) + LINES_AFTER).min(lines.len());
+    lines[start..end]
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let text = if line.text.is_empty() {
+                "♪"
+            } else {
+                line.text.as_str()
+            };
+            if current == Some(start + i) {
+                format!("**▶ {}**", text)
+            } else {
+                text.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Splits lyrics into pages of at most `limit` characters, between stanzas
+/// where possible and otherwise between lines.
+fn split_pages(text: &str, limit: usize) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    for stanza in text.trim().split("\n\n") {
+        let stanza = stanza.trim_matches('\n');
+        if stanza.is_empty() {
+            continue;
+        }
+        let pieces = if stanza.chars().count() <= limit {
+            vec![stanza.to_string()]
+        } else {
+            split_lines(stanza, limit)
+        };
+        for (i, piece) in pieces.into_iter().enumerate() {
+            let separator = if i == 0 { "\n\n" } else { "\n" };
+            if page.is_empty() {
+                page = piece;
+            } else if page.chars().count() + separator.len() + piece.chars().count() > limit {
+                pages.push(std::mem::replace(&mut page, piece));
+            } else {
+                page.push_str(separator);
+                page.push_str(&piece);
+            }
+        }
+    }
+    if !page.is_empty() || pages.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+/// Splits a stanza too long for one page into pieces of whole lines, cutting
+/// only lines that are too long by themselves.
+fn split_lines(stanza: &str, limit: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut piece = String::new();
+    for line in stanza.lines() {
+        let chars: Vec<char> = line.chars().collect();
+        for part in chars.chunks(limit.max(1)) {
+            let part: String = part.iter().collect();
+            if piece.is_empty() {
+                piece = part;
+            } else if piece.chars().count() + 1 + part.chars().count() > limit {
+                pieces.push(std::mem::replace(&mut piece, part));
+            } else {
+                piece.push('\n');
+                piece.push_str(&part);
+            }
+        }
+    }
+    if !piece.is_empty() {
+        pieces.push(piece);
+    }
+    pieces
+}
+
+/// The timed lines of an LRC file, in order. A line can have several
+/// timestamps, and tags like `[ar:Artist]` are skipped.
+pub(super) fn parse_lrc(lrc: &str) -> Vec<LrcLine> {
+    let mut lines = Vec::new();
+    for line in lrc.lines() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+        while let Some(tag) = rest.strip_prefix('[') {
+            let Some((stamp, after)) = tag.split_once(']') else {
+                break;
+            };
+            let Some(at) = parse_lrc_time(stamp) else {
+                break;
+            };
+            times.push(at);
+            rest = after;
+        }
+        let text = rest.trim();
+        lines.extend(times.into_iter().map(|at| LrcLine {
+            at,
+            text: text.to_string(),
+        }));
+    }
+    lines.sort_by_key(|line| line.at);
+    lines
+}
+
+/// `mm:ss.xx` as used by LRC timestamps.
+fn parse_lrc_time(stamp: &str) -> Option<Duration> {
+    let (mins, secs) = stamp.split_once(':')?;
+    let mins: u64 = mins.trim().parse().ok()?;
+    let secs: f64 = secs.trim().parse().ok()?;
+    if !(0.0..60.0).contains(&secs) {
+        return None;
+    }
+    Some(Duration::from_secs(mins * 60) + Duration::from_secs_f64(secs))
+}
+
+/// The text of timed lines, for providers that only have those.
+pub(super) fn plain_from_lrc(lines: &[LrcLine]) -> String {
+    lines
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Drops `(Official Video)`, `[Lyrics]` and the like.
+fn strip_brackets(text: &str) -> String {
+    let mut kept = String::new();
+    let mut depth = 0usize;
+    for c in text.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => kept.push(c),
+            _ => {}
+        }
+    }
+    kept.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Turns uploader names like `Artist - Topic` or `ArtistVEVO` into the artist.
+fn clean_artist(uploader: &str) -> String {
+    uploader
+        .trim()
+        .trim_end_matches(" - Topic")
+        .trim_end_matches("VEVO")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn track(title: &str, artist: Option<&str>) -> TrackInfo {
+        TrackInfo {
+            title: title.to_string(),
+            url: String::new(),
+            artist: artist.map(str::to_string),
+            duration: Some(Duration::from_secs(200)),
+            thumbnail: None,
+            requester: None,
+            source: "YouTube".to_string(),
+            enqueued_at: Utc::now(),
+            stream_title: None,
+            autoplay: false,
+        }
+    }
+
+    #[test]
+    fn parses_lrc_lines_in_order() {
+        let lrc = "[ar:Someone]\n[ti:Song]\n[00:12.50]First\n[00:05.00][01:02.25]Chorus\n[00:20.00]\nnot timed";
+        let lines = parse_lrc(lrc);
+        let times: Vec<_> = lines.iter().map(|line| line.at.as_millis()).collect();
+        assert_eq!(times, [5_000, 12_500, 20_000, 62_250]);
+        assert_eq!(lines[0].text, "Chorus");
+        assert_eq!(lines[1].text, "First");
+        assert_eq!(lines[2].text, "");
+    }
+
+    #[test]
+    fn synced_window_marks_the_current_line() {
+        let lines = parse_lrc("[00:01.00]One\n[00:02.00]Two\n[00:03.00]Three");
+        assert_eq!(synced_window(&lines, Duration::ZERO), "One\nTwo\nThree");
+        assert_eq!(
+            synced_window(&lines, Duration::from_millis(2_500)),
+            "One\n**▶ Two**\nThree"
+        );
+    }
+
+    #[test]
+    fn pages_stay_under_the_limit() {
+        let stanza = (1..=8)
+            .map(|i| format!("This is line number {} of a rather long verse", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let text = vec![stanza; 40].join("\n\n");
+        let pages = split_pages(&text, PAGE_LIMIT);
+        assert!(pages.len() > 1);
+        assert!(pages.iter().all(|page| page.chars().count() <= PAGE_LIMIT));
+        // Pages break between stanzas, so none starts mid-verse.
+        assert!(pages
+            .iter()
+            .all(|page| page.starts_with("This is line number 1 ")));
+        assert_eq!(pages.join("\n\n"), text);
+    }
+
+    #[test]
+    fn splits_lines_longer_than_a_page() {
+        let text = "é".repeat(PAGE_LIMIT * 2 + 10);
+        let pages = split_pages(&text, PAGE_LIMIT);
+        let sizes: Vec<_> = pages.iter().map(|page| page.chars().count()).collect();
+        assert_eq!(sizes, [PAGE_LIMIT, PAGE_LIMIT, 10]);
+    }
+
+    #[test]
+    fn query_from_video_title() {
+        let query = LyricsQuery::from_track(&track(
+            "Daft Punk - Around the World (Official Audio) [HD]",
+            Some("DaftPunkVEVO"),
+        ));
+        assert_eq!(query.artist.as_deref(), Some("Daft Punk"));
+        assert_eq!(query.title, "Around the World");
+        assert_eq!(query.duration, Some(Duration::from_secs(200)));
+
+        let query = LyricsQuery::from_track(&track("Harder, Better", Some("Daft Punk - Topic")));
+        assert_eq!(query.artist.as_deref(), Some("Daft Punk"));
+        assert_eq!(query.title, "Harder, Better");
+    }
+}
@@ -1,2 +1,20 @@
+mod autoplay;
 pub mod cmds;
-mod disconnect_handler;
+mod controls;
+pub mod disconnect_handler;
+mod dj;
+mod dsp;
+mod files;
+mod filters;
+mod history;
+mod lyrics;
+mod pages;
+pub mod persist;
+mod playback;
+mod playlist;
+mod queue;
+pub mod radio;
+pub mod saved_playlists;
+mod search;
+pub mod session;
+mod sources;
@@ -0,0 +1,76 @@
+use crate::{Context, Error};
+use poise::futures_util::StreamExt;
+use serenity::all::{
+    ButtonStyle, ComponentInteractionCollector, CreateActionRow, CreateButton,
+    CreateInteractionResponse, CreateInteractionResponseMessage,
+};
+use serenity::builder::CreateEmbed;
+use std::time::Duration;
+use tracing::error;
+
+/// How long the page buttons keep working after the last press.
+const PAGE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Replies with the first of `pages` embeds, with previous/next buttons to
+/// flip through the rest for as long as someone keeps pressing them.
+pub(super) async fn paginate(
+    ctx: Context<'_>,
+    pages: usize,
+    render: impl Fn(usize) -> CreateEmbed,
+) -> Result<(), Error> {
+    let prev_id = format!("{}prev", ctx.id());
+    let next_id = format!("{}next", ctx.id());
+    let buttons = |page: usize| {
+        vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(&prev_id)
+                .label("Previous")
+                .style(ButtonStyle::Secondary)
+                .disabled(page == 0),
+            CreateButton::new(&next_id)
+                .label("Next")
+                .style(ButtonStyle::Secondary)
+                .disabled(page + 1 >= pages),
+        ])]
+    };
+
+    let mut page = 0;
+    let mut reply = poise::CreateReply::default().embed(render(page));
+    if pages > 1 {
+        reply = reply.components(buttons(page));
+    }
+    let handle = ctx.send(reply).await?;
+    if pages <= 1 {
+        return Ok(());
+    }
+
+    let ctx_id = ctx.id().to_string();
+    let mut presses = ComponentInteractionCollector::new(ctx)
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id))
+        .timeout(PAGE_TIMEOUT)
+        .stream();
+    while let Some(press) = presses.next().await {
+        if press.data.custom_id == next_id {
+            page = (page + 1).min(pages - 1);
+        } else if press.data.custom_id == prev_id {
+            page = page.saturating_sub(1);
+        }
+        let msg = CreateInteractionResponseMessage::new()
+            .embed(render(page))
+            .components(buttons(page));
+        if let Err(why) = press
+            .create_response(ctx, CreateInteractionResponse::UpdateMessage(msg))
+            .await
+        {
+            error!("Err turning page {:?}", why);
+        }
+    }
+
+    // Drop the buttons once nobody is listening for them anymore.
+    let reply = poise::CreateReply::default()
+        .embed(render(page))
+        .components(vec![]);
+    if let Err(why) = handle.edit(ctx, reply).await {
+        error!("Err removing page buttons {:?}", why);
+    }
+    Ok(())
+}
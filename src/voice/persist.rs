@@ -0,0 +1,220 @@
+use crate::db::Database;
+use crate::models::queue::{QueueSnapshot, SavedTrack};
+use crate::voice::cmds::{
+    check_msg, enqueue_info, format_duration, get_http_client, get_metadata, guild_volume,
+    join_channel, join_vc, source_for, TrackInfo,
+};
+use crate::voice::{controls, filters, session};
+use crate::{Context, Error};
+use serenity::all::Mentionable;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+use serenity::model::prelude::*;
+use songbird::Songbird;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Set once the queues were saved for shutdown, so tracks stopping as the bot
+/// goes down don't overwrite them.
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Saves a guild's queue so `/voice restore` can bring it back after a restart.
+pub async fn save(db: &Database, manager: &Songbird, guild_id: GuildId) {
+    if SHUTTING_DOWN.load(Ordering::Relaxed) {
+        return;
+    }
+    // Without a call there's nothing to compare against, `leave` clears the
+    // saved queue itself.
+    let Some(snapshot) = snapshot(manager, guild_id).await else {
+        return;
+    };
+    if let Err(why) = db.save_queue(&snapshot) {
+        error!("Err saving queue for guild {} {:?}", guild_id, why);
+    }
+}
+
+/// Saves the queue of every guild the bot is playing in, before shutting down.
+pub async fn save_all(db: &Database, manager: &Songbird) {
+    let guild_ids: Vec<_> = manager
+        .iter()
+        .map(|(guild_id, _)| GuildId::new(guild_id.0.get()))
+        .collect();
+    info!("Saving {} queues", guild_ids.len());
+    for guild_id in guild_ids {
+        save(db, manager, guild_id).await;
+    }
+    SHUTTING_DOWN.store(true, Ordering::Relaxed);
+}
+
+async fn snapshot(manager: &Songbird, guild_id: GuildId) -> Option<QueueSnapshot> {
+    let (queue, channel_id) = {
+        let handler_lock = manager.get(guild_id)?;
+        let handler = handler_lock.lock().await;
+        let channel_id = handler
+            .current_channel()
+            .map(|channel_id| ChannelId::new(channel_id.0.get()));
+        (handler.queue().current_queue(), channel_id)
+    };
+
+    let mut position = None;
+    let mut tracks = Vec::new();
+    for track in &queue {
+        // A track that just ended can still be at the front of the queue.
+        let state = match track.get_info().await {
+            Ok(state) if !state.playing.is_done() => state,
+            _ => continue,
+        };
+        let info = get_metadata(track).await;
+        if !info.is_replayable() {
+            continue;
+        }
+        if position.is_none() {
+            position = Some(filters::media_position(track, state.position).await);
+        }
+        tracks.push(SavedTrack::from(info));
+    }
+
+    let (loop_mode, fair_queue) = {
+        let session = session::get(guild_id).await;
+        let session = session.lock().await;
+        (session.loop_mode, session.fair_queue)
+    };
+    Some(QueueSnapshot {
+        guild_id,
+        channel_id,
+        loop_mode,
+        fair_queue,
+        position: position.unwrap_or(Duration::ZERO),
+        tracks,
+    })
+}
+
+/// Brings back the queue saved before the bot last restarted
+#[poise::command(slash_command, guild_only)]
+pub async fn restore(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(snapshot) = ctx.data().db.load_queue(guild_id)? else {
+        check_msg(ctx.say("There is no saved queue.").await);
+        return Ok(());
+    };
+    ctx.defer().await?;
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let mut moved = None;
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        if !handler.queue().is_empty() {
+            check_msg(
+                ctx.say("Something is already queued, stop it to restore the saved queue.")
+                    .await,
+            );
+            return Ok(());
+        }
+        let current = handler
+            .current_channel()
+            .map(|channel_id| ChannelId::new(channel_id.0.get()));
+        if let (Some(saved), Some(current)) = (snapshot.channel_id, current) {
+            if saved != current {
+                check_msg(
+                    ctx.say(format!(
+                        "The saved queue was playing in {}, move me there to restore it.",
+                        saved.mention()
+                    ))
+                    .await,
+                );
+                return Ok(());
+            }
+        }
+    } else {
+        // Goes back to the channel the queue was playing in, or to the
+        // caller's if that's gone.
+        let rejoined = match snapshot.channel_id {
+            Some(channel_id) => join_channel(ctx, manager.clone(), guild_id, channel_id)
+                .await
+                .is_ok(),
+            None => false,
+        };
+        if !rejoined {
+            if let Err(why) = join_vc(ctx, manager.clone()).await {
+                check_msg(ctx.reply(why).await);
+            } else if let Some(saved) = snapshot.channel_id {
+                moved = Some(format!(
+                    "Couldn't rejoin {}, playing here instead.",
+                    saved.mention()
+                ));
+            }
+        }
+    }
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        // Restored once, from here on the queue is saved as it plays.
+        ctx.data().db.delete_queue(guild_id)?;
+        let http_client = get_http_client(&ctx).await;
+        let volume = guild_volume(ctx, guild_id).await;
+        session::get(guild_id).await.lock().await.fair_queue = snapshot.fair_queue;
+
+        let mut handler = handler_lock.lock().await;
+        let resume_at = handler.queue().is_empty() && !snapshot.position.is_zero();
+        let mut count = 0;
+        let mut duration = Duration::ZERO;
+        let mut first = None;
+        for (i, track) in snapshot.tracks.into_iter().enumerate() {
+            let info = TrackInfo::from(track);
+            let source = match source_for(http_client.clone(), &info) {
+                Ok(source) => source,
+                Err(why) => {
+                    warn!("Not restoring {}: {}", info.title, why);
+                    continue;
+                }
+            };
+            count += 1;
+            duration += info.duration.unwrap_or_default();
+            let handle = enqueue_info(&mut handler, guild_id, source, info, volume).await;
+            // The saved position belongs to the first track only.
+            if i == 0 {
+                first = Some(handle);
+            }
+        }
+        session::set_loop_mode(guild_id, handler.queue().current(), snapshot.loop_mode).await;
+        drop(handler);
+
+        let mut embed = CreateEmbed::new()
+            .colour(0xeb984e)
+            .title("rauly.rs | Queue restored")
+            .description(format!(
+                "Queued {} tracks, {}",
+                count,
+                format_duration(duration)
+            ))
+            .footer(CreateEmbedFooter::new("rauly.rs"));
+        if let Some(moved) = moved {
+            embed = embed.field("Channel", moved, false);
+        }
+        if let Some(handle) = first.filter(|_| resume_at) {
+            // Picks up where the track was when the queue was saved, once
+            // it has loaded.
+            let target = filters::playback_position(&handle, snapshot.position).await;
+            if let Err(why) = handle.seek_async(target).await {
+                error!("Err restoring position for guild {} {:?}", guild_id, why);
+                embed = embed.field(
+                    "Position",
+                    format!(
+                        "Couldn't resume at {}, playing from the start.",
+                        format_duration(snapshot.position)
+                    ),
+                    false,
+                );
+            }
+        }
+        check_msg(ctx.send(poise::CreateReply::default().embed(embed)).await);
+        controls::show(ctx, manager).await;
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
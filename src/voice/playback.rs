@@ -0,0 +1,275 @@
+use crate::voice::cmds::{check_msg, format_duration, get_metadata};
+use crate::voice::{dj, filters};
+use crate::{Context, Error};
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+use songbird::tracks::TrackHandle;
+use std::time::Duration;
+use tracing::error;
+
+const PROGRESS_BAR_WIDTH: usize = 20;
+
+/// Jumps to a position in the current track
+#[poise::command(slash_command, guild_only)]
+pub async fn seek(
+    ctx: Context<'_>,
+    #[description = "Position like 1:30, 1:02:03 or 90"] position: String,
+) -> Result<(), Error> {
+    let Some(position) = parse_timestamp(&position) else {
+        check_msg(ctx.say("Position should look like `1:30` or `90`.").await);
+        return Ok(());
+    };
+    seek_current(ctx, |_| position).await
+}
+
+/// Skips forward in the current track
+#[poise::command(slash_command, guild_only)]
+pub async fn forward(
+    ctx: Context<'_>,
+    #[description = "Seconds to skip forward"]
+    #[min = 1]
+    secs: u64,
+) -> Result<(), Error> {
+    seek_current(ctx, |current| current + Duration::from_secs(secs)).await
+}
+
+/// Goes back in the current track
+#[poise::command(slash_command, guild_only)]
+pub async fn rewind(
+    ctx: Context<'_>,
+    #[description = "Seconds to go back"]
+    #[min = 1]
+    secs: u64,
+) -> Result<(), Error> {
+    seek_current(ctx, |current| {
+        current.saturating_sub(Duration::from_secs(secs))
+    })
+    .await
+}
+
+/// Shows the current track and how far into it we are
+#[poise::command(slash_command, guild_only)]
+pub async fn nowplaying(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let track = handler_lock.lock().await.queue().current();
+        let Some(track) = track else {
+            check_msg(ctx.say("Nothing is playing.").await);
+            return Ok(());
+        };
+
+        let embed = progress_embed(&track).await;
+        let msg = poise::CreateReply::default().embed(embed);
+        check_msg(ctx.send(msg).await);
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+/// Seeks the current track to wherever `target` puts it, given its position.
+async fn seek_current(
+    ctx: Context<'_>,
+    target: impl FnOnce(Duration) -> Duration,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let track = handler_lock.lock().await.queue().current();
+        let Some(track) = track else {
+            check_msg(ctx.say("Nothing is playing.").await);
+            return Ok(());
+        };
+        ctx.defer().await?;
+
+        let current = match track.get_info().await {
+            Ok(state) => filters::media_position(&track, state.position).await,
+            Err(why) => {
+                error!("Err getting track info {:?}", why);
+                return Ok(());
+            }
+        };
+        let position = target(current);
+        if let Some(total) = get_metadata(&track).await.duration {
+            if position >= total {
+                check_msg(
+                    ctx.say(format!(
+                        "The track is only {} long.",
+                        format_duration(total)
+                    ))
+                    .await,
+                );
+                return Ok(());
+            }
+        }
+
+        let seek_to = filters::playback_position(&track, position).await;
+        match track.seek_async(seek_to).await {
+            Ok(_) => {
+                let embed = progress_embed(&track).await;
+                let msg = poise::CreateReply::default()
+                    .content(format!("Jumped to {}.", format_duration(position)))
+                    .embed(embed);
+                check_msg(ctx.send(msg).await);
+            }
+            Err(why) => {
+                error!("Err seeking source {:?}", why);
+                check_msg(ctx.say("This track can't be seeked.").await);
+            }
+        }
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+async fn progress_embed(track: &TrackHandle) -> CreateEmbed {
+    let metadata = get_metadata(track).await;
+    let elapsed = match track.get_info().await {
+        Ok(state) => filters::media_position(track, state.position).await,
+        Err(_) => Duration::ZERO,
+    };
+
+    let progress = match metadata.duration {
+        Some(total) => format!(
+            "{} `{} / {}`",
+            progress_bar(elapsed, total),
+            format_duration(elapsed),
+            format_duration(total)
+        ),
+        None => format!("`{}`", format_duration(elapsed)),
+    };
+
+    let footer = CreateEmbedFooter::new("rauly.rs");
+    let mut embed = CreateEmbed::new()
+        .colour(0xeb984e)
+        .title(format!("Now Playing | {}", metadata.title))
+        .description(progress)
+        .footer(footer);
+    if metadata.url.starts_with("http") {
+        embed = embed.url(metadata.url);
+    }
+    embed
+}
+
+/// Draws something like `▬▬▬▬▬🔘▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬`.
+fn progress_bar(elapsed: Duration, total: Duration) -> String {
+    let filled = if total.is_zero() {
+        0
+    } else {
+        let ratio = elapsed.as_secs_f64() / total.as_secs_f64();
+        ((ratio * PROGRESS_BAR_WIDTH as f64) as usize).min(PROGRESS_BAR_WIDTH - 1)
+    };
+    let mut bar = "▬".repeat(filled);
+    bar.push('🔘');
+    bar.push_str(&"▬".repeat(PROGRESS_BAR_WIDTH - 1 - filled));
+    bar
+}
+
+/// Parses `ss`, `m:ss` or `h:mm:ss`.
+fn parse_timestamp(input: &str) -> Option<Duration> {
+    let parts = input
+        .trim()
+        .split(':')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if parts.is_empty() || parts.len() > 3 {
+        return None;
+    }
+    // Only the first part may go past 59, as in `90` or `75:00`.
+    if parts[1..].iter().any(|&part| part >= 60) {
+        return None;
+    }
+    let secs = parts.iter().fold(0, |total, part| total * 60 + part);
+    Some(Duration::from_secs(secs))
+}
+
+/// Sets the playback volume for this server
+#[poise::command(slash_command, guild_only)]
+pub async fn volume(
+    ctx: Context<'_>,
+    #[description = "Volume in percent"]
+    #[min = 0]
+    #[max = 200]
+    percent: u32,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
+    let name = ctx.guild().map(|g| g.name.clone()).unwrap_or_default();
+    ctx.data()
+        .guilds
+        .update(guild_id, &name, |guild| guild.volume = percent)
+        .await?;
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    // Queued tracks already got the old default when they were added.
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        for track in handler.queue().current_queue() {
+            if let Err(why) = track.set_volume(percent as f32 / 100.0) {
+                error!("Err setting volume {:?}", why);
+            }
+        }
+    }
+
+    check_msg(ctx.say(format!("Volume set to {}%.", percent)).await);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(input: &str) -> Option<u64> {
+        parse_timestamp(input).map(|duration| duration.as_secs())
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(secs("90"), Some(90));
+        assert_eq!(secs("1:30"), Some(90));
+        assert_eq!(secs("1:02:03"), Some(3723));
+        assert_eq!(secs(" 0:05 "), Some(5));
+        assert_eq!(secs("75:00"), Some(4500));
+    }
+
+    #[test]
+    fn edges() {
+        assert_eq!(secs("0"), Some(0));
+        assert_eq!(secs("0:59"), Some(59));
+        assert_eq!(secs("1:59:59"), Some(7199));
+    }
+
+    #[test]
+    fn rejects_bad_timestamps() {
+        assert_eq!(secs("1:99"), None);
+        assert_eq!(secs("1:60"), None);
+        assert_eq!(secs("0:75:00"), None);
+        assert_eq!(secs("1:2:3:4"), None);
+        assert_eq!(secs(""), None);
+        assert_eq!(secs("1:"), None);
+        assert_eq!(secs("-5"), None);
+        assert_eq!(secs("1:3o"), None);
+    }
+}
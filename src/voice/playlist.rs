@@ -0,0 +1,249 @@
+use crate::voice::cmds::{
+    check_msg, enqueue_info, format_duration, get_http_client, guild_volume, source_for, TrackInfo,
+};
+use crate::voice::persist;
+use crate::voice::queue::interleave_by_requester;
+use crate::voice::session;
+use crate::{Context, Error};
+use anyhow::{bail, Result};
+use chrono::Utc;
+use reqwest::Url;
+use serde::Deserialize;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+use serenity::prelude::Mutex;
+use songbird::{Call, Songbird};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{error, warn};
+
+/// Most tracks a single playlist link will queue.
+const PLAYLIST_LIMIT: usize = 100;
+/// How long yt-dlp gets to list a playlist.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What `yt-dlp --flat-playlist -J` prints, trimmed to what we use.
+#[derive(Deserialize)]
+struct FlatPlaylist {
+    title: Option<String>,
+    playlist_count: Option<usize>,
+    #[serde(default)]
+    entries: Vec<FlatEntry>,
+}
+
+#[derive(Deserialize)]
+struct FlatEntry {
+    url: Option<String>,
+    title: Option<String>,
+    duration: Option<f64>,
+    uploader: Option<String>,
+    channel: Option<String>,
+    #[serde(default)]
+    thumbnails: Vec<Thumbnail>,
+}
+
+#[derive(Deserialize)]
+struct Thumbnail {
+    url: String,
+}
+
+impl FlatEntry {
+    fn into_info(mut self) -> Option<TrackInfo> {
+        let url = self.url?;
+        Some(TrackInfo {
+            title: self.title.unwrap_or_else(|| "Unknown track".to_string()),
+            source: TrackInfo::source_name(&url),
+            url,
+            artist: self.uploader.or(self.channel),
+            duration: self
+                .duration
+                .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                .map(Duration::from_secs_f64),
+            thumbnail: self.thumbnails.pop().map(|thumbnail| thumbnail.url),
+            requester: None,
+            enqueued_at: Utc::now(),
+            stream_title: None,
+            autoplay: false,
+        })
+    }
+}
+
+/// Whether a link points at a playlist or album rather than a single track.
+/// Watch links that only carry a playlist or mix along (`watch?v=X&list=RD…`)
+/// are single tracks.
+pub(super) fn is_playlist(url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+    if !url.scheme().starts_with("http") {
+        return false;
+    }
+    let path = url.path();
+    if ["/sets/", "/album/"]
+        .iter()
+        .any(|marker| path.contains(marker))
+    {
+        return true;
+    }
+    if path.starts_with("/playlist") {
+        return true;
+    }
+    // youtu.be links name their video in the path rather than in `v=`.
+    let is_short_link = url.host_str() == Some("youtu.be");
+    let has = |key: &str| url.query_pairs().any(|(name, _)| name == key);
+    has("list") && !has("v") && !is_short_link
+}
+
+/// Queues every track of a playlist, resolving each one only when it's played.
+pub(super) async fn enqueue(
+    ctx: Context<'_>,
+    manager: &Songbird,
+    handler_lock: &Arc<Mutex<Call>>,
+    url: &str,
+) -> Result<(), Error> {
+    let playlist = match fetch(url).await {
+        Ok(playlist) => playlist,
+        Err(why) => {
+            error!("Err loading playlist {:?}", why);
+            check_msg(ctx.say("Couldn't load that playlist.").await);
+            return Ok(());
+        }
+    };
+    let total = playlist.playlist_count;
+    let tracks: Vec<_> = playlist
+        .entries
+        .into_iter()
+        .filter_map(FlatEntry::into_info)
+        .map(|info| TrackInfo {
+            requester: Some(ctx.author().id),
+            ..info
+        })
+        .collect();
+    if tracks.is_empty() {
+        check_msg(ctx.say("That playlist is empty.").await);
+        return Ok(());
+    }
+
+    let tracks = enqueue_all(ctx, manager, handler_lock, &tracks).await;
+
+    let title = playlist.title.as_deref().unwrap_or("Playlist");
+    let mut embed = summary_embed(title, &tracks).url(url);
+    if total.is_some_and(|total| total > PLAYLIST_LIMIT) {
+        embed = embed.field(
+            "Limit",
+            format!("Only the first {} tracks were queued.", PLAYLIST_LIMIT),
+            false,
+        );
+    }
+    check_msg(ctx.send(poise::CreateReply::default().embed(embed)).await);
+    Ok(())
+}
+
+/// Queues tracks whose metadata is already known, like a playlist's, then
+/// saves the queue. Returns the tracks that could be queued.
+pub(super) async fn enqueue_all(
+    ctx: Context<'_>,
+    manager: &Songbird,
+    handler_lock: &Arc<Mutex<Call>>,
+    tracks: &[TrackInfo],
+) -> Vec<TrackInfo> {
+    let guild_id = ctx.guild_id().unwrap();
+    let http_client = get_http_client(&ctx).await;
+    let volume = guild_volume(ctx, guild_id).await;
+    let mut handler = handler_lock.lock().await;
+    let mut queued = Vec::with_capacity(tracks.len());
+    for info in tracks {
+        let source = match source_for(http_client.clone(), info) {
+            Ok(source) => source,
+            Err(why) => {
+                warn!("Not queueing {}: {}", info.title, why);
+                continue;
+            }
+        };
+        enqueue_info(&mut handler, guild_id, source, info.clone(), volume).await;
+        queued.push(info.clone());
+    }
+    if session::get(guild_id).await.lock().await.fair_queue {
+        interleave_by_requester(handler.queue()).await;
+    }
+    drop(handler);
+    persist::save(&ctx.data().db, manager, guild_id).await;
+    queued
+}
+
+/// The tracks of a playlist or mix, with nobody as their requester.
+pub(super) async fn tracks(url: &str) -> Result<Vec<TrackInfo>> {
+    let playlist = fetch(url).await?;
+    Ok(playlist
+        .entries
+        .into_iter()
+        .filter_map(FlatEntry::into_info)
+        .collect())
+}
+
+/// "Queued 37 tracks, 2:14:05" for a batch of tracks.
+pub(super) fn summary_embed(title: &str, tracks: &[TrackInfo]) -> CreateEmbed {
+    let duration: Duration = tracks.iter().filter_map(|info| info.duration).sum();
+    let footer = CreateEmbedFooter::new("rauly.rs");
+    let mut embed = CreateEmbed::new()
+        .colour(0xeb984e)
+        .title(format!("rauly.rs | {}", title))
+        .description(format!(
+            "Queued {} tracks, {}",
+            tracks.len(),
+            format_duration(duration)
+        ))
+        .footer(footer);
+    if let Some(thumbnail) = tracks.iter().find_map(|info| info.thumbnail.clone()) {
+        embed = embed.thumbnail(thumbnail);
+    }
+    embed
+}
+
+async fn fetch(url: &str) -> Result<FlatPlaylist> {
+    let output = Command::new("yt-dlp")
+        .args(["--flat-playlist", "-J", "--playlist-end"])
+        .arg(PLAYLIST_LIMIT.to_string())
+        .arg(url)
+        // Giving up on a hung yt-dlp drops it, which kills it.
+        .kill_on_drop(true)
+        .output();
+    let Ok(output) = tokio::time::timeout(FETCH_TIMEOUT, output).await else {
+        bail!("yt-dlp took longer than {:?}", FETCH_TIMEOUT);
+    };
+    let output = output?;
+    if !output.status.success() {
+        bail!(
+            "yt-dlp failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playlist_links() {
+        assert!(is_playlist(
+            "https://www.youtube.com/playlist?list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG"
+        ));
+        assert!(is_playlist(
+            "https://music.youtube.com/playlist?list=OLAK5uy_abc"
+        ));
+        assert!(is_playlist("https://soundcloud.com/artist/sets/best-of"));
+        assert!(is_playlist("https://www.youtube.com/watch?list=PLabc"));
+    }
+
+    #[test]
+    fn watch_links_with_a_list_are_single_tracks() {
+        assert!(!is_playlist(
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=RDdQw4w9WgXcQ"
+        ));
+        assert!(!is_playlist("https://youtu.be/dQw4w9WgXcQ?list=PLabc"));
+        assert!(!is_playlist("https://www.youtube.com/watch?v=dQw4w9WgXcQ"));
+        assert!(!is_playlist("never gonna give you up list="));
+    }
+}
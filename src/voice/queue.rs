@@ -0,0 +1,480 @@
+use crate::voice::cmds::{
+    check_msg, format_duration, get_metadata, guild_volume, title_link, TrackInfo,
+};
+use crate::voice::session::{self, LoopMode};
+use crate::voice::{dj, filters, pages, persist};
+use crate::{Context, Error};
+use poise::ChoiceParameter;
+use rand::seq::SliceRandom;
+use serenity::all::Mentionable;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+use serenity::model::prelude::UserId;
+use songbird::tracks::{TrackHandle, TrackQueue};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::error;
+
+const PAGE_SIZE: usize = 10;
+
+/// Removes the track at a position in the queue
+#[poise::command(slash_command, guild_only)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Position in the queue"]
+    #[min = 1]
+    position: usize,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        // Position 0 is the track that's playing, `skip` takes care of that one.
+        let removed = match position {
+            0 => None,
+            _ => handler.queue().dequeue(position),
+        };
+        match removed {
+            Some(track) => {
+                let title = get_metadata(&track).await.title;
+                let _ = track.stop();
+                check_msg(
+                    ctx.say(format!("Removed **{}** from the queue.", title))
+                        .await,
+                );
+            }
+            None => check_msg(ctx.say(format!("No track at position {}.", position)).await),
+        }
+        drop(handler);
+        persist::save(&ctx.data().db, &manager, guild_id).await;
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+/// Moves a track to another position in the queue
+#[poise::command(slash_command, guild_only, rename = "move")]
+pub async fn move_track(
+    ctx: Context<'_>,
+    #[description = "Current position of the track"]
+    #[min = 1]
+    from: usize,
+    #[description = "Position to move the track to"]
+    #[min = 1]
+    to: usize,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        let moved = handler.queue().modify_queue(|queue| {
+            if from == 0 || to == 0 || from >= queue.len() || to >= queue.len() {
+                return None;
+            }
+            let track = queue.remove(from)?;
+            let handle = track.handle();
+            queue.insert(to, track);
+            Some(handle)
+        });
+        match moved {
+            Some(track) => {
+                let title = get_metadata(&track).await.title;
+                check_msg(
+                    ctx.say(format!("Moved **{}** to position {}.", title, to))
+                        .await,
+                );
+            }
+            None => check_msg(
+                ctx.say(format!(
+                    "Can't move from {} to {}, the queue has {} upcoming tracks.",
+                    from,
+                    to,
+                    handler.queue().len().saturating_sub(1)
+                ))
+                .await,
+            ),
+        }
+        drop(handler);
+        persist::save(&ctx.data().db, &manager, guild_id).await;
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+/// Shuffles the upcoming tracks in the queue
+#[poise::command(slash_command, guild_only)]
+pub async fn shuffle(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        let shuffled = shuffle_upcoming(handler.queue());
+        check_msg(ctx.say(format!("Shuffled {} tracks.", shuffled)).await);
+        drop(handler);
+        persist::save(&ctx.data().db, &manager, guild_id).await;
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+/// Clears the upcoming tracks but keeps the current one playing
+#[poise::command(slash_command, guild_only)]
+pub async fn clear(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+    if !dj::require_may_clear(ctx, &manager).await {
+        return Ok(());
+    }
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        let removed = handler.queue().modify_queue(|queue| {
+            let removed: Vec<_> = queue.drain(1.min(queue.len())..).collect();
+            for track in &removed {
+                let _ = track.stop();
+            }
+            removed.len()
+        });
+        check_msg(
+            ctx.say(format!("Cleared {} tracks from the queue.", removed))
+                .await,
+        );
+        drop(handler);
+        persist::save(&ctx.data().db, &manager, guild_id).await;
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+/// Skips ahead to the track at a position in the queue
+#[poise::command(slash_command, guild_only)]
+pub async fn jump(
+    ctx: Context<'_>,
+    #[description = "Position in the queue"]
+    #[min = 1]
+    position: usize,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+    // Jumping drops the tracks in between, which may be other members'.
+    if !dj::require_may_clear(ctx, &manager).await {
+        return Ok(());
+    }
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        // Drop everything between the current track and the target, then skip
+        // the current one so the target starts playing.
+        let target = handler.queue().modify_queue(|queue| {
+            if position == 0 || position >= queue.len() {
+                return None;
+            }
+            for track in queue.drain(1..position) {
+                let _ = track.stop();
+            }
+            queue.get(1).map(|track| track.handle())
+        });
+        let Some(target) = target else {
+            check_msg(ctx.say(format!("No track at position {}.", position)).await);
+            return Ok(());
+        };
+
+        let title = get_metadata(&target).await.title;
+        if let Err(why) = handler.queue().skip() {
+            error!("Err skip source {:?}", why);
+            return Ok(());
+        }
+        drop(handler);
+        check_msg(ctx.say(format!("Jumped to **{}**.", title)).await);
+        persist::save(&ctx.data().db, &manager, guild_id).await;
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+/// Repeats the current track or the whole queue
+#[poise::command(slash_command, guild_only, rename = "loop")]
+pub async fn loop_mode(
+    ctx: Context<'_>,
+    #[description = "What to repeat"] mode: LoopMode,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        session::set_loop_mode(guild_id, handler.queue().current(), mode).await;
+
+        let reply = match mode {
+            LoopMode::Off => "Looping is off.",
+            LoopMode::Track => "Looping the current track.",
+            LoopMode::Queue => "Looping the queue.",
+        };
+        check_msg(ctx.say(reply).await);
+        drop(handler);
+        persist::save(&ctx.data().db, &manager, guild_id).await;
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+/// Lets requesters take turns instead of playing tracks in the order they came
+#[poise::command(slash_command, guild_only)]
+pub async fn fair(
+    ctx: Context<'_>,
+    #[description = "Whether to interleave tracks by requester"] enabled: bool,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if !dj::require_same_channel(ctx).await {
+        return Ok(());
+    }
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        session::get(guild_id).await.lock().await.fair_queue = enabled;
+        let reply = if enabled {
+            interleave_by_requester(handler.queue()).await;
+            "Requesters now take turns."
+        } else {
+            "Tracks play in the order they were added."
+        };
+        check_msg(ctx.say(reply).await);
+        drop(handler);
+        persist::save(&ctx.data().db, &manager, guild_id).await;
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+/// Reorders the upcoming tracks round-robin by requester, keeping each
+/// requester's own tracks in order. Whoever is playing right now goes last in
+/// the first round.
+pub(super) async fn interleave_by_requester(queue: &TrackQueue) {
+    let tracks = queue.current_queue();
+    let Some(current) = tracks.first() else {
+        return;
+    };
+    let mut requesters = HashMap::new();
+    for track in &tracks[1..] {
+        requesters.insert(track.uuid(), get_metadata(track).await.requester);
+    }
+    let mut turns: HashMap<Option<UserId>, usize> = HashMap::new();
+    turns.insert(get_metadata(current).await.requester, 1);
+
+    queue.modify_queue(|queue| {
+        if queue.len() < 3 {
+            return;
+        }
+        let mut upcoming: Vec<_> = queue
+            .drain(1..)
+            .enumerate()
+            .map(|(i, track)| {
+                let requester = requesters.get(&track.uuid()).copied().flatten();
+                let turn = turns.entry(requester).or_default();
+                *turn += 1;
+                (*turn, i, track)
+            })
+            .collect();
+        upcoming.sort_by_key(|(turn, i, _)| (*turn, *i));
+        queue.extend(upcoming.into_iter().map(|(_, _, track)| track));
+    });
+}
+
+/// Shuffles everything after the current track, returning how many tracks moved.
+pub(super) fn shuffle_upcoming(queue: &TrackQueue) -> usize {
+    queue.modify_queue(|queue| {
+        if queue.len() < 2 {
+            return 0;
+        }
+        let upcoming = &mut queue.make_contiguous()[1..];
+        upcoming.shuffle(&mut rand::thread_rng());
+        upcoming.len()
+    })
+}
+
+/// Replies with the queue, paged with previous/next buttons.
+pub(super) async fn show(ctx: Context<'_>, tracks: &[TrackHandle]) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(current) = tracks.first() else {
+        let embed = CreateEmbed::new()
+            .colour(0xeb984e)
+            .title("Music Queue")
+            .description("The queue is empty. Add something with `/voice play`.")
+            .footer(CreateEmbedFooter::new("rauly.rs"));
+        check_msg(ctx.send(poise::CreateReply::default().embed(embed)).await);
+        return Ok(());
+    };
+
+    let mut infos = Vec::with_capacity(tracks.len());
+    for track in tracks {
+        infos.push(get_metadata(track).await);
+    }
+    let elapsed = match current.get_info().await {
+        Ok(state) => filters::media_position(current, state.position).await,
+        Err(_) => Duration::ZERO,
+    };
+    let (loop_mode, fair_queue) = {
+        let session = session::get(guild_id).await;
+        let session = session.lock().await;
+        (session.loop_mode, session.fair_queue)
+    };
+    let summary = QueueSummary {
+        loop_mode,
+        fair_queue,
+        volume: guild_volume(ctx, guild_id).await,
+        elapsed,
+    };
+
+    let pages = infos.len().div_ceil(PAGE_SIZE);
+    pages::paginate(ctx, pages, |page| queue_page(&infos, &summary, page)).await
+}
+
+pub(super) struct QueueSummary {
+    pub loop_mode: LoopMode,
+    pub fair_queue: bool,
+    pub volume: f32,
+    /// How far into the current track playback is.
+    pub elapsed: Duration,
+}
+
+pub(super) fn queue_page(infos: &[TrackInfo], summary: &QueueSummary, page: usize) -> CreateEmbed {
+    let pages = infos.len().div_ceil(PAGE_SIZE);
+    let mut lines = Vec::new();
+    for (i, info) in infos
+        .iter()
+        .enumerate()
+        .skip(page * PAGE_SIZE)
+        .take(PAGE_SIZE)
+    {
+        let duration = match (i, info.duration) {
+            (0, Some(total)) => format!(
+                "{} / {}",
+                format_duration(summary.elapsed),
+                format_duration(total)
+            ),
+            (_, Some(total)) => format_duration(total),
+            (_, None) => "?".to_string(),
+        };
+        let mut line = if i == 0 {
+            format!(
+                "**Now Playing:** {} `{}`",
+                title_link(&info.title, &info.url),
+                duration
+            )
+        } else {
+            format!(
+                "**{}**. {} `{}`",
+                i,
+                title_link(&info.title, &info.url),
+                duration
+            )
+        };
+        if info.autoplay {
+            line += " · autoplay";
+        } else if let Some(requester) = info.requester {
+            line += &format!(" · {}", requester.mention());
+        }
+        lines.push(line);
+    }
+
+    // Tracks without a known duration (like streams) can't be added up.
+    let mut remaining = Duration::ZERO;
+    let mut exact = true;
+    for (i, info) in infos.iter().enumerate() {
+        match info.duration {
+            Some(total) if i == 0 => remaining += total.saturating_sub(summary.elapsed),
+            Some(total) => remaining += total,
+            None => exact = false,
+        }
+    }
+    let mut remaining = format_duration(remaining);
+    if !exact {
+        remaining.push('+');
+    }
+
+    let footer = CreateEmbedFooter::new(format!("rauly.rs | Page {}/{}", page + 1, pages));
+    CreateEmbed::new()
+        .colour(0xeb984e)
+        .title("Music Queue")
+        .description(lines.join("\n"))
+        .field("Tracks", infos.len().to_string(), true)
+        .field("Remaining", remaining, true)
+        .field("Loop", summary.loop_mode.name(), true)
+        .field(
+            "Fair Queue",
+            if summary.fair_queue { "On" } else { "Off" },
+            true,
+        )
+        .field("Volume", format!("{:.0}%", summary.volume * 100.0), true)
+        .footer(footer)
+}
@@ -0,0 +1,593 @@
+use crate::models::radio::Station;
+use crate::voice::cmds::{check_msg, enqueue, get_http_client, join_vc, TrackInfo, TrackInfoKey};
+use crate::voice::controls;
+use crate::voice::dj::Listener;
+use crate::{Context, Error};
+use anyhow::Result;
+use chrono::Utc;
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, CONTENT_TYPE};
+use reqwest::{Client as HttpClient, Url};
+use serenity::all::Mentionable;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use songbird::input::{Compose, HlsRequest, HttpRequest};
+use songbird::tracks::TrackHandle;
+use songbird::Songbird;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, warn};
+
+/// `TrackInfo::source` of radio stations and other live streams.
+pub const RADIO_SOURCE: &str = "Radio";
+
+/// How often the title watcher checks that its track still plays, and how
+/// long it waits before connecting to the station again.
+const STREAM_TITLE_INTERVAL: Duration = Duration::from_secs(20);
+/// How long to wait for a station before giving up on it, for now.
+const STREAM_TIMEOUT: Duration = Duration::from_secs(10);
+/// Station playlists (`.pls`, `.m3u`) and live HLS playlists are small,
+/// anything bigger is not one.
+const STATION_PLAYLIST_LIMIT: usize = 64 * 1024;
+/// Extensions of links that are worth asking whether they're a stream.
+const STREAM_EXTENSIONS: &[&str] = &["aac", "aacp", "m3u", "m3u8", "mp3", "ogg", "opus", "pls"];
+/// Words in the paths of stream links, like `/live` or `/radio1.stream`.
+const STREAM_WORDS: &[&str] = &["icecast", "listen", "live", "radio", "stream"];
+
+/// Listen to internet radio stations saved for this server
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("play", "save", "remove", "list"),
+    subcommand_required
+)]
+pub async fn radio(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+async fn autocomplete_station(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Vec::new();
+    };
+    let partial = partial.to_lowercase();
+    match ctx.data().db.stations(guild_id) {
+        Ok(stations) => stations
+            .into_iter()
+            .map(|station| station.name)
+            .filter(|name| name.to_lowercase().contains(&partial))
+            .take(25)
+            .collect(),
+        Err(why) => {
+            error!("Err listing radio stations {:?}", why);
+            Vec::new()
+        }
+    }
+}
+
+/// Tune in to a saved station, or any stream link
+#[poise::command(slash_command, guild_only)]
+async fn play(
+    ctx: Context<'_>,
+    #[description = "Saved station, or a link to a stream"]
+    #[autocomplete = "autocomplete_station"]
+    station: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let station = station.trim();
+    let (name, url) = match ctx.data().db.find_station(guild_id, station)? {
+        Some(saved) => (Some(saved.name), saved.url),
+        None if station.starts_with("http") => (None, station.to_string()),
+        None => {
+            check_msg(
+                ctx.say(format!("There is no station called **{}**.", station))
+                    .await,
+            );
+            return Ok(());
+        }
+    };
+    ctx.defer().await?;
+
+    let http_client = get_http_client(&ctx).await;
+    match detect(&http_client, &url).await {
+        Some(stream) => play_stream(ctx, stream, name).await,
+        None => {
+            check_msg(ctx.say("That link doesn't lead to a radio stream.").await);
+            Ok(())
+        }
+    }
+}
+
+/// Save a station for everyone in this server
+#[poise::command(slash_command, guild_only)]
+async fn save(
+    ctx: Context<'_>,
+    #[description = "Name of the station"]
+    #[max_length = 100]
+    name: String,
+    #[description = "Link to the stream, or to its .pls or .m3u file"] url: String,
+) -> Result<(), Error> {
+    let url = url.trim().to_string();
+    if !url.starts_with("http") {
+        check_msg(ctx.say("Give a link to the stream.").await);
+        return Ok(());
+    }
+    ctx.defer().await?;
+    let http_client = get_http_client(&ctx).await;
+    if detect(&http_client, &url).await.is_none() {
+        check_msg(ctx.say("That link doesn't lead to a radio stream.").await);
+        return Ok(());
+    }
+
+    let station = Station {
+        guild_id: ctx.guild_id().unwrap(),
+        name: name.trim().to_string(),
+        url,
+        added_by: ctx.author().id,
+    };
+    let reply = if ctx.data().db.save_station(&station)? {
+        format!("Saved **{}**.", station.name)
+    } else {
+        format!("There already is a station called **{}**.", station.name)
+    };
+    check_msg(ctx.say(reply).await);
+    Ok(())
+}
+
+/// Remove a saved station
+#[poise::command(slash_command, guild_only)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Station to remove"]
+    #[autocomplete = "autocomplete_station"]
+    name: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(station) = ctx.data().db.find_station(guild_id, name.trim())? else {
+        check_msg(
+            ctx.say(format!("There is no station called **{}**.", name))
+                .await,
+        );
+        return Ok(());
+    };
+    let is_dj = Listener::from_ctx(ctx)
+        .await
+        .is_some_and(|listener| listener.is_dj);
+    if station.added_by != ctx.author().id && !is_dj {
+        check_msg(
+            ctx.say(format!(
+                "Only {} and DJs can remove **{}**.",
+                station.added_by.mention(),
+                station.name
+            ))
+            .await,
+        );
+        return Ok(());
+    }
+
+    ctx.data().db.delete_station(guild_id, &station.name)?;
+    check_msg(ctx.say(format!("Removed **{}**.", station.name)).await);
+    Ok(())
+}
+
+/// List the saved stations
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let stations = ctx.data().db.stations(ctx.guild_id().unwrap())?;
+    let description = if stations.is_empty() {
+        "No stations yet. Add one with `/radio save`.".to_string()
+    } else {
+        stations
+            .iter()
+            .map(|station| format!("**{}** · <{}>", station.name, station.url))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let embed = CreateEmbed::new()
+        .colour(0xeb984e)
+        .title("Radio Stations")
+        .description(description)
+        .footer(CreateEmbedFooter::new("rauly.rs"));
+    check_msg(ctx.send(poise::CreateReply::default().embed(embed)).await);
+    Ok(())
+}
+
+/// A live stream found behind a link.
+pub(super) struct LiveStream {
+    /// Where the audio itself is, after following station playlists.
+    pub url: String,
+    /// What the station calls itself, if it says.
+    pub name: Option<String>,
+}
+
+/// Checks whether a link is a radio stream rather than a page or a file,
+/// following `.pls` and `.m3u` station playlists to the stream inside.
+pub(super) async fn detect(client: &HttpClient, url: &str) -> Option<LiveStream> {
+    let mut url = url.to_string();
+    // One level of station playlist is all anyone uses.
+    for _ in 0..2 {
+        if is_hls(&url) {
+            return is_live_hls(client, &url)
+                .await
+                .then_some(LiveStream { url, name: None });
+        }
+        let request = client.get(&url).header("Icy-MetaData", "1").send();
+        let response = match tokio::time::timeout(STREAM_TIMEOUT, request).await {
+            Ok(Ok(response)) if response.status().is_success() => response,
+            Ok(Ok(response)) => {
+                debug!("Not a stream, {} answered {}", url, response.status());
+                return None;
+            }
+            Ok(Err(why)) => {
+                debug!("Not a stream, {} failed {:?}", url, why);
+                return None;
+            }
+            Err(_) => return None,
+        };
+
+        let headers = response.headers();
+        let content_type = header(headers, CONTENT_TYPE.as_str())
+            .and_then(|value| value.split(';').next().map(str::to_lowercase))
+            .unwrap_or_default();
+        if is_station_playlist(&url, &content_type) {
+            url = station_playlist_entry(response).await?;
+            continue;
+        }
+
+        let is_icy = headers.keys().any(|name| name.as_str().starts_with("icy-"));
+        // Plain audio files have a length, live streams go on forever.
+        let is_endless = header(headers, CONTENT_LENGTH.as_str()).is_none();
+        let is_audio = content_type.starts_with("audio/") || content_type == "application/ogg";
+        if is_icy || (is_audio && is_endless) {
+            let name = header(headers, "icy-name").filter(|name| !name.is_empty());
+            return Some(LiveStream { url, name });
+        }
+        return None;
+    }
+    None
+}
+
+/// Queues a live stream and joins the caller if needed.
+pub(super) async fn play_stream(
+    ctx: Context<'_>,
+    stream: LiveStream,
+    name: Option<String>,
+) -> Result<(), Error> {
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+    let guild_id = ctx.guild_id().unwrap();
+
+    if manager.get(guild_id).is_none() {
+        if let Err(why) = join_vc(ctx, manager.clone()).await {
+            check_msg(ctx.reply(why).await);
+        }
+    }
+
+    let Some(handler_lock) = manager.get(guild_id) else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+        return Ok(());
+    };
+    let title = name
+        .or(stream.name)
+        .or_else(|| {
+            Url::parse(&stream.url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+        })
+        .unwrap_or_else(|| "Radio".to_string());
+    let info = TrackInfo {
+        title,
+        url: stream.url,
+        artist: None,
+        duration: None,
+        thumbnail: None,
+        requester: Some(ctx.author().id),
+        source: RADIO_SOURCE.to_string(),
+        enqueued_at: Utc::now(),
+        stream_title: None,
+        autoplay: false,
+    };
+    let source = source(get_http_client(&ctx).await, &info.url);
+    enqueue(ctx, manager.clone(), &handler_lock, source, info).await;
+    Ok(())
+}
+
+/// Streams a station straight over HTTP, without yt-dlp.
+pub(super) fn source(client: HttpClient, url: &str) -> Box<dyn Compose> {
+    if is_hls(url) {
+        Box::new(HlsRequest::new(client, url.to_string()))
+    } else {
+        Box::new(HttpRequest::new(client, url.to_string()))
+    }
+}
+
+/// Follows what the station says is on air while a radio track plays,
+/// updating the track and the now-playing message when it changes. The titles
+/// come from one connection that stays open for as long as the track plays.
+pub(super) fn watch_stream_title(
+    track: TrackHandle,
+    client: HttpClient,
+    http: Arc<Http>,
+    manager: Arc<Songbird>,
+    guild_id: GuildId,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let Some(url) = track
+            .typemap()
+            .read()
+            .await
+            .get::<TrackInfoKey>()
+            .map(|info| info.url.clone())
+        else {
+            return;
+        };
+        if is_hls(&url) {
+            return;
+        }
+
+        let mut last = None;
+        let mut reader = None;
+        loop {
+            match track.get_info().await {
+                Ok(state) if !state.playing.is_done() => {}
+                _ => break,
+            }
+            let icy = match &mut reader {
+                Some(icy) => icy,
+                None => {
+                    let connect = IcyReader::connect(&client, &url);
+                    match tokio::time::timeout(STREAM_TIMEOUT, connect).await {
+                        Ok(Ok(Some(icy))) => reader.insert(icy),
+                        // The station doesn't say what's playing.
+                        Ok(Ok(None)) => break,
+                        Ok(Err(why)) => {
+                            warn!("Err reading stream title {:?}", why);
+                            tokio::time::sleep(STREAM_TITLE_INTERVAL).await;
+                            continue;
+                        }
+                        Err(_) => {
+                            warn!("Timed out reading stream title from {}", url);
+                            tokio::time::sleep(STREAM_TITLE_INTERVAL).await;
+                            continue;
+                        }
+                    }
+                }
+            };
+            // Gives up waiting every so often to see if the track still plays.
+            let title = match tokio::time::timeout(STREAM_TITLE_INTERVAL, icy.next_title()).await {
+                Ok(Ok(Some(title))) => title,
+                Ok(Ok(None)) => {
+                    reader = None;
+                    continue;
+                }
+                Ok(Err(why)) => {
+                    warn!("Err reading stream title {:?}", why);
+                    reader = None;
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            let title = Some(title).filter(|title| !title.is_empty());
+            if title != last {
+                if let Some(info) = track.typemap().write().await.get_mut::<TrackInfoKey>() {
+                    info.stream_title = title.clone();
+                }
+                controls::refresh(&http, &manager, guild_id).await;
+                last = title;
+            }
+        }
+    })
+}
+
+/// Reads the ICY metadata a station sends in between its audio.
+struct IcyReader {
+    response: reqwest::Response,
+    /// Bytes of audio between metadata blocks.
+    interval: usize,
+    /// What was received but not looked at yet, starting right after the
+    /// last metadata block.
+    buffer: Vec<u8>,
+}
+
+impl IcyReader {
+    /// Connects to a station asking for metadata. Returns `None` if the
+    /// station doesn't send any.
+    async fn connect(client: &HttpClient, url: &str) -> Result<Option<Self>> {
+        let response = client
+            .get(url)
+            .header("Icy-MetaData", "1")
+            .send()
+            .await?
+            .error_for_status()?;
+        let Some(interval) =
+            header(response.headers(), "icy-metaint").and_then(|v| v.parse::<usize>().ok())
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            response,
+            interval,
+            buffer: Vec::new(),
+        }))
+    }
+
+    /// The next `StreamTitle` the station sends, or `None` once the stream
+    /// ends.
+    async fn next_title(&mut self) -> Result<Option<String>> {
+        loop {
+            // The metadata comes after `interval` bytes of audio, with its
+            // length in 16 byte blocks in front of it. Most blocks are empty.
+            if let Some(&blocks) = self.buffer.get(self.interval) {
+                let end = self.interval + 1 + blocks as usize * 16;
+                if self.buffer.len() >= end {
+                    let metadata =
+                        String::from_utf8_lossy(&self.buffer[self.interval + 1..end]).into_owned();
+                    self.buffer.drain(..end);
+                    match parse_stream_title(&metadata) {
+                        Some(title) => return Ok(Some(title)),
+                        None => continue,
+                    }
+                }
+            }
+            match self.response.chunk().await? {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Picks the title out of metadata like `StreamTitle='Artist - Song';`.
+fn parse_stream_title(metadata: &str) -> Option<String> {
+    let start = metadata.find("StreamTitle='")? + "StreamTitle='".len();
+    let end = start + metadata[start..].find("';")?;
+    Some(metadata[start..end].trim().to_string())
+}
+
+/// Whether a link looks like it could lead to a stream or a station playlist,
+/// going by its extension or words like `live` in its path.
+pub(super) fn looks_like_stream(url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+    let path = url.path().to_lowercase();
+    let extension = path
+        .rsplit_once('/')
+        .and_then(|(_, name)| name.rsplit_once('.'))
+        .map(|(_, extension)| extension);
+    if extension.is_some_and(|extension| STREAM_EXTENSIONS.contains(&extension)) {
+        return true;
+    }
+    // Shoutcast servers stream from their root, on a port of their own.
+    if url.port().is_some() && matches!(path.as_str(), "/" | "/;") {
+        return true;
+    }
+    STREAM_WORDS.iter().any(|word| path.contains(word))
+}
+
+fn is_hls(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| url.path().ends_with(".m3u8"))
+}
+
+/// Whether an HLS playlist is live and keeps growing, rather than a recording
+/// that's played through. Master playlists go by their first variant.
+async fn is_live_hls(client: &HttpClient, url: &str) -> bool {
+    let mut url = url.to_string();
+    for _ in 0..2 {
+        let request = client.get(&url).send();
+        let response = match tokio::time::timeout(STREAM_TIMEOUT, request).await {
+            Ok(Ok(response)) if response.status().is_success() => response,
+            _ => return false,
+        };
+        // Recordings list every segment, live playlists only the latest few.
+        let Some(body) = read_small(response).await else {
+            return false;
+        };
+        match hls_variant(&body) {
+            Some(variant) => match Url::parse(&url).and_then(|base| base.join(variant)) {
+                Ok(variant) => url = variant.to_string(),
+                Err(_) => return false,
+            },
+            None => return !is_finished_hls(&body),
+        }
+    }
+    false
+}
+
+/// The first variant stream a master playlist lists, if it is one.
+fn hls_variant(playlist: &str) -> Option<&str> {
+    let mut lines = playlist.lines().map(str::trim);
+    lines.find(|line| line.starts_with("#EXT-X-STREAM-INF"))?;
+    lines.find(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Whether a media playlist is complete, as recordings are.
+fn is_finished_hls(playlist: &str) -> bool {
+    playlist
+        .lines()
+        .map(str::trim)
+        .any(|line| line == "#EXT-X-ENDLIST" || line == "#EXT-X-PLAYLIST-TYPE:VOD")
+}
+
+fn is_station_playlist(url: &str, content_type: &str) -> bool {
+    let path = Url::parse(url)
+        .map(|url| url.path().to_lowercase())
+        .unwrap_or_default();
+    path.ends_with(".pls")
+        || path.ends_with(".m3u")
+        || matches!(
+            content_type,
+            "audio/x-scpls" | "audio/x-mpegurl" | "audio/mpegurl"
+        )
+}
+
+/// The first stream listed in a `.pls` or `.m3u` file.
+async fn station_playlist_entry(response: reqwest::Response) -> Option<String> {
+    let body = read_small(response).await?;
+    body.lines().find_map(|line| {
+        // `.pls` lines look like `File1=http://...`, `.m3u` ones are bare.
+        let line = line.trim();
+        let url = match line.split_once('=') {
+            Some((key, value)) if key.to_lowercase().starts_with("file") => value,
+            _ => line,
+        };
+        url.starts_with("http").then(|| url.to_string())
+    })
+}
+
+/// The body of a playlist, or `None` if it's too big to be one.
+async fn read_small(mut response: reqwest::Response) -> Option<String> {
+    let mut body = Vec::new();
+    while let Ok(Some(chunk)) = response.chunk().await {
+        body.extend_from_slice(&chunk);
+        if body.len() > STATION_PLAYLIST_LIMIT {
+            return None;
+        }
+    }
+    Some(String::from_utf8_lossy(&body).into_owned())
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_looking_links() {
+        assert!(looks_like_stream("https://example.com/live/aac"));
+        assert!(looks_like_stream("http://radio.example.com:8000/;"));
+        assert!(looks_like_stream("https://example.com/station.pls"));
+        assert!(looks_like_stream("https://example.com/hls/index.m3u8"));
+        assert!(!looks_like_stream("https://example.com/blog/post"));
+        assert!(!looks_like_stream("https://vimeo.com/76979871"));
+    }
+
+    #[test]
+    fn finished_hls_playlists_are_not_live() {
+        let live =
+            "#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXTINF:6.0,\nseg1.ts\n#EXTINF:6.0,\nseg2.ts\n";
+        assert!(!is_finished_hls(live));
+        let recording = format!("{}#EXT-X-ENDLIST\n", live);
+        assert!(is_finished_hls(&recording));
+        assert!(is_finished_hls(
+            "#EXTM3U\n#EXT-X-PLAYLIST-TYPE:VOD\nseg1.ts\n"
+        ));
+    }
+
+    #[test]
+    fn master_playlists_lead_to_their_first_variant() {
+        let master = "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=128000\nlow/index.m3u8\n\
+                      #EXT-X-STREAM-INF:BANDWIDTH=256000\nhigh/index.m3u8\n";
+        assert_eq!(hls_variant(master), Some("low/index.m3u8"));
+        assert_eq!(hls_variant("#EXTM3U\n#EXTINF:6.0,\nseg1.ts\n"), None);
+    }
+}
@@ -0,0 +1,357 @@
+use crate::models::playlist::{Playlist, PlaylistScope};
+use crate::models::queue::SavedTrack;
+use crate::voice::cmds::{
+    check_msg, format_duration, get_http_client, get_metadata, join_vc, title_link, TrackInfo,
+};
+use crate::voice::controls;
+use crate::voice::dj::Listener;
+use crate::voice::playlist::{enqueue_all, summary_embed};
+use crate::{Context, Error};
+use chrono::Utc;
+use poise::ChoiceParameter;
+use serenity::all::Mentionable;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+use songbird::input::{Compose, YoutubeDl};
+use tracing::error;
+
+/// How many tracks `/playlist show` lists.
+const SHOW_LIMIT: usize = 20;
+
+/// Keep tracks for later, for yourself or the whole server
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("create", "add", "remove", "list", "show", "play", "delete"),
+    subcommand_required
+)]
+pub async fn playlist(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+async fn autocomplete_playlist(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Vec::new();
+    };
+    let partial = partial.to_lowercase();
+    match ctx.data().db.playlists(ctx.author().id, guild_id) {
+        Ok(playlists) => playlists
+            .into_iter()
+            .map(|playlist| playlist.name)
+            .filter(|name| name.to_lowercase().contains(&partial))
+            .take(25)
+            .collect(),
+        Err(why) => {
+            error!("Err listing playlists {:?}", why);
+            Vec::new()
+        }
+    }
+}
+
+/// Create an empty playlist
+#[poise::command(slash_command, guild_only)]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "Name of the playlist"]
+    #[max_length = 100]
+    name: String,
+    #[description = "Who it's for, just you by default"] scope: Option<PlaylistScope>,
+) -> Result<(), Error> {
+    let name = name.trim();
+    if name.is_empty() {
+        check_msg(ctx.say("Give the playlist a name.").await);
+        return Ok(());
+    }
+    let guild_id = match scope.unwrap_or(PlaylistScope::Personal) {
+        PlaylistScope::Personal => None,
+        PlaylistScope::Guild => ctx.guild_id(),
+    };
+    let reply = match ctx
+        .data()
+        .db
+        .create_playlist(ctx.author().id, guild_id, name)?
+    {
+        Some(playlist) => format!(
+            "Created {} playlist **{}**.",
+            playlist.scope().name().to_lowercase(),
+            playlist.name
+        ),
+        None => format!("There already is a playlist called **{}**.", name),
+    };
+
+    check_msg(ctx.say(reply).await);
+    Ok(())
+}
+
+/// Add a track to a playlist
+#[poise::command(slash_command, guild_only)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "Playlist to add to"]
+    #[autocomplete = "autocomplete_playlist"]
+    name: String,
+    #[description = "A link, or \"current\" for the track that's playing"] track: String,
+) -> Result<(), Error> {
+    let Some(playlist) = find_editable(ctx, &name).await? else {
+        return Ok(());
+    };
+    ctx.defer().await?;
+
+    let info = if track.trim().eq_ignore_ascii_case("current") {
+        let manager = songbird::get(ctx.serenity_context())
+            .await
+            .expect("Songbird Voice client placed in at initialisation.")
+            .clone();
+        let current = match manager.get(ctx.guild_id().unwrap()) {
+            Some(handler_lock) => handler_lock.lock().await.queue().current(),
+            None => None,
+        };
+        let Some(current) = current else {
+            check_msg(ctx.say("Nothing is playing.").await);
+            return Ok(());
+        };
+        get_metadata(&current).await
+    } else if track.starts_with("http") {
+        let http_client = get_http_client(&ctx).await;
+        match YoutubeDl::new(http_client, track).aux_metadata().await {
+            Ok(metadata) => TrackInfo::from(metadata),
+            Err(why) => {
+                error!("Err getting metadata {:?}", why);
+                check_msg(ctx.say("Couldn't load that track.").await);
+                return Ok(());
+            }
+        }
+    } else {
+        check_msg(
+            ctx.say("Give a link, or `current` for the track that's playing.")
+                .await,
+        );
+        return Ok(());
+    };
+    if !info.is_replayable() {
+        check_msg(ctx.say("That track can't be saved.").await);
+        return Ok(());
+    }
+
+    let title = info.title.clone();
+    let track = SavedTrack {
+        requester: Some(ctx.author().id),
+        enqueued_at: Utc::now(),
+        ..SavedTrack::from(info)
+    };
+    ctx.data().db.add_playlist_track(playlist.id, &track)?;
+    check_msg(
+        ctx.say(format!("Added **{}** to **{}**.", title, playlist.name))
+            .await,
+    );
+    Ok(())
+}
+
+/// Remove a track from a playlist
+#[poise::command(slash_command, guild_only)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Playlist to remove from"]
+    #[autocomplete = "autocomplete_playlist"]
+    name: String,
+    #[description = "Position of the track, as in /playlist show"]
+    #[min = 1]
+    position: usize,
+) -> Result<(), Error> {
+    let Some(playlist) = find_editable(ctx, &name).await? else {
+        return Ok(());
+    };
+    let reply = match ctx.data().db.remove_playlist_track(playlist.id, position)? {
+        Some(track) => format!("Removed **{}** from **{}**.", track.title, playlist.name),
+        None => format!("No track at position {}.", position),
+    };
+
+    check_msg(ctx.say(reply).await);
+    Ok(())
+}
+
+/// List your playlists and the server's
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let playlists = ctx
+        .data()
+        .db
+        .playlists(ctx.author().id, ctx.guild_id().unwrap())?;
+    let lines = |scope: PlaylistScope| {
+        let lines: Vec<_> = playlists
+            .iter()
+            .filter(|playlist| playlist.scope() == scope)
+            .map(|playlist| format!("**{}** · {} tracks", playlist.name, playlist.track_count))
+            .collect();
+        if lines.is_empty() {
+            "None".to_string()
+        } else {
+            lines.join("\n")
+        }
+    };
+
+    let embed = CreateEmbed::new()
+        .colour(0xeb984e)
+        .title("Playlists")
+        .field("Yours", lines(PlaylistScope::Personal), false)
+        .field("Server", lines(PlaylistScope::Guild), false)
+        .footer(CreateEmbedFooter::new("rauly.rs"));
+    check_msg(ctx.send(poise::CreateReply::default().embed(embed)).await);
+    Ok(())
+}
+
+/// Show the tracks in a playlist
+#[poise::command(slash_command, guild_only)]
+async fn show(
+    ctx: Context<'_>,
+    #[description = "Playlist to show"]
+    #[autocomplete = "autocomplete_playlist"]
+    name: String,
+) -> Result<(), Error> {
+    let Some(playlist) = find(ctx, &name).await? else {
+        return Ok(());
+    };
+    let tracks = ctx.data().db.playlist_tracks(playlist.id)?;
+
+    let mut lines: Vec<_> = tracks
+        .iter()
+        .take(SHOW_LIMIT)
+        .enumerate()
+        .map(|(i, track)| {
+            let duration = track.duration.map_or("?".to_string(), format_duration);
+            format!(
+                "**{}**. {} `{}`",
+                i + 1,
+                title_link(&track.title, &track.url),
+                duration
+            )
+        })
+        .collect();
+    if tracks.len() > SHOW_LIMIT {
+        lines.push(format!("…and {} more", tracks.len() - SHOW_LIMIT));
+    }
+    let description = if lines.is_empty() {
+        "This playlist is empty. Add tracks with `/playlist add`.".to_string()
+    } else {
+        lines.join("\n")
+    };
+
+    let embed = CreateEmbed::new()
+        .colour(0xeb984e)
+        .title(format!("Playlist | {}", playlist.name))
+        .description(description)
+        .field("Owner", playlist.owner_id.mention().to_string(), true)
+        .field("Shared with", playlist.scope().name(), true)
+        .footer(CreateEmbedFooter::new("rauly.rs"));
+    check_msg(ctx.send(poise::CreateReply::default().embed(embed)).await);
+    Ok(())
+}
+
+/// Queue every track in a playlist
+#[poise::command(slash_command, guild_only)]
+async fn play(
+    ctx: Context<'_>,
+    #[description = "Playlist to play"]
+    #[autocomplete = "autocomplete_playlist"]
+    name: String,
+) -> Result<(), Error> {
+    let Some(playlist) = find(ctx, &name).await? else {
+        return Ok(());
+    };
+    let tracks: Vec<_> = ctx
+        .data()
+        .db
+        .playlist_tracks(playlist.id)?
+        .into_iter()
+        .map(|track| TrackInfo {
+            requester: Some(ctx.author().id),
+            enqueued_at: Utc::now(),
+            ..TrackInfo::from(track)
+        })
+        .collect();
+    if tracks.is_empty() {
+        check_msg(ctx.say("That playlist is empty.").await);
+        return Ok(());
+    }
+    ctx.defer().await?;
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+    let guild_id = ctx.guild_id().unwrap();
+
+    if manager.get(guild_id).is_none() {
+        if let Err(why) = join_vc(ctx, manager.clone()).await {
+            check_msg(ctx.reply(why).await);
+        }
+    }
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let queued = enqueue_all(ctx, &manager, &handler_lock, &tracks).await;
+        let embed = summary_embed(&playlist.name, &queued);
+        check_msg(ctx.send(poise::CreateReply::default().embed(embed)).await);
+        controls::show(ctx, manager.clone()).await;
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+/// Delete a playlist
+#[poise::command(slash_command, guild_only)]
+async fn delete(
+    ctx: Context<'_>,
+    #[description = "Playlist to delete"]
+    #[autocomplete = "autocomplete_playlist"]
+    name: String,
+) -> Result<(), Error> {
+    let Some(playlist) = find_editable(ctx, &name).await? else {
+        return Ok(());
+    };
+    ctx.data().db.delete_playlist(playlist.id)?;
+
+    check_msg(ctx.say(format!("Deleted **{}**.", playlist.name)).await);
+    Ok(())
+}
+
+/// Looks up a playlist the caller can see, telling them if there's none.
+async fn find(ctx: Context<'_>, name: &str) -> Result<Option<Playlist>, Error> {
+    let playlist =
+        ctx.data()
+            .db
+            .find_playlist(ctx.author().id, ctx.guild_id().unwrap(), name.trim())?;
+    if playlist.is_none() {
+        check_msg(
+            ctx.say(format!("There is no playlist called **{}**.", name))
+                .await,
+        );
+    }
+    Ok(playlist)
+}
+
+/// Like [`find`], but only for playlists the caller may change: their own, or
+/// server playlists if they're a DJ.
+async fn find_editable(ctx: Context<'_>, name: &str) -> Result<Option<Playlist>, Error> {
+    let Some(playlist) = find(ctx, name).await? else {
+        return Ok(None);
+    };
+    if playlist.owner_id == ctx.author().id {
+        return Ok(Some(playlist));
+    }
+    if Listener::from_ctx(ctx)
+        .await
+        .is_some_and(|listener| listener.is_dj)
+    {
+        return Ok(Some(playlist));
+    }
+    check_msg(
+        ctx.say(format!(
+            "Only {} and DJs can change **{}**.",
+            playlist.owner_id.mention(),
+            playlist.name
+        ))
+        .await,
+    );
+    Ok(None)
+}
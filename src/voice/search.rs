@@ -0,0 +1,169 @@
+use crate::voice::cmds::{
+    check_msg, enqueue, format_duration, get_http_client, join_vc, TrackInfo,
+};
+use crate::{Context, Error};
+use poise::futures_util::StreamExt;
+use serenity::all::{
+    ComponentInteractionCollector, ComponentInteractionDataKind, CreateActionRow,
+    CreateInteractionResponse, CreateInteractionResponseMessage, CreateSelectMenu,
+    CreateSelectMenuKind, CreateSelectMenuOption,
+};
+use songbird::input::{AuxMetadata, YoutubeDl};
+use std::time::Duration;
+use tracing::error;
+
+const SEARCH_RESULTS: usize = 5;
+/// How long the picker waits for a choice.
+const PICK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Search for a track and pick which result to play
+#[poise::command(slash_command, guild_only)]
+pub async fn search(
+    ctx: Context<'_>,
+    #[description = "What to search for"] query: String,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let http_client = get_http_client(&ctx).await;
+    let results = match YoutubeDl::new_search(http_client.clone(), query)
+        .search(Some(SEARCH_RESULTS))
+        .await
+    {
+        Ok(results) => results,
+        Err(why) => {
+            error!("Err searching {:?}", why);
+            check_msg(ctx.say("Search failed, try again.").await);
+            return Ok(());
+        }
+    };
+    let results: Vec<_> = results
+        .into_iter()
+        .filter(|result| result.source_url.is_some())
+        .collect();
+    if results.is_empty() {
+        check_msg(ctx.say("No results.").await);
+        return Ok(());
+    }
+
+    let menu_id = format!("{}pick", ctx.id());
+    let options = results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| result_option(i, result))
+        .collect();
+    let menu = CreateSelectMenu::new(&menu_id, CreateSelectMenuKind::String { options })
+        .placeholder("Pick a track");
+    let reply = poise::CreateReply::default()
+        .content("Search results:")
+        .components(vec![CreateActionRow::SelectMenu(menu)]);
+    let handle = ctx.send(reply).await?;
+
+    let author = ctx.author().id;
+    let mut presses = ComponentInteractionCollector::new(ctx)
+        .filter(move |press| press.data.custom_id == menu_id)
+        .timeout(PICK_TIMEOUT)
+        .stream();
+    let mut pick = None;
+    while let Some(press) = presses.next().await {
+        if press.user.id == author {
+            pick = Some(press);
+            break;
+        }
+        // Answered so others don't see the interaction fail.
+        let msg = CreateInteractionResponseMessage::new()
+            .content("Only whoever searched can pick a result.")
+            .ephemeral(true);
+        if let Err(why) = press
+            .create_response(ctx, CreateInteractionResponse::Message(msg))
+            .await
+        {
+            error!("Err responding to search pick {:?}", why);
+        }
+    }
+    let Some(pick) = pick else {
+        let reply = poise::CreateReply::default()
+            .content("Search timed out.")
+            .components(vec![]);
+        if let Err(why) = handle.edit(ctx, reply).await {
+            error!("Err closing search picker {:?}", why);
+        }
+        return Ok(());
+    };
+
+    let chosen = match &pick.data.kind {
+        ComponentInteractionDataKind::StringSelect { values } => values
+            .first()
+            .and_then(|value| value.parse::<usize>().ok())
+            .and_then(|i| results.get(i)),
+        _ => None,
+    };
+    let Some(chosen) = chosen.cloned() else {
+        return Ok(());
+    };
+    let msg = CreateInteractionResponseMessage::new()
+        .content(format!(
+            "Picked **{}**.",
+            chosen.title.as_deref().unwrap_or("Title")
+        ))
+        .components(vec![]);
+    if let Err(why) = pick
+        .create_response(ctx, CreateInteractionResponse::UpdateMessage(msg))
+        .await
+    {
+        error!("Err responding to search pick {:?}", why);
+    }
+
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+    let guild_id = ctx.guild_id().unwrap();
+
+    if manager.get(guild_id).is_none() {
+        if let Err(why) = join_vc(ctx, manager.clone()).await {
+            check_msg(ctx.reply(why).await);
+        }
+    }
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let url = chosen.source_url.clone().unwrap_or_default();
+        let source = Box::new(YoutubeDl::new(http_client, url));
+        enqueue(
+            ctx,
+            manager.clone(),
+            &handler_lock,
+            source,
+            TrackInfo::from(chosen),
+        )
+        .await;
+    } else {
+        check_msg(ctx.say("Not in a voice channel.").await);
+    }
+
+    Ok(())
+}
+
+fn result_option(i: usize, result: &AuxMetadata) -> CreateSelectMenuOption {
+    // Discord caps option labels and descriptions at 100 characters.
+    let label: String = result
+        .title
+        .as_deref()
+        .unwrap_or("Title")
+        .chars()
+        .take(100)
+        .collect();
+    let mut description = result.artist.clone().unwrap_or_default();
+    if let Some(duration) = result.duration {
+        if !description.is_empty() {
+            description += " · ";
+        }
+        description += &format_duration(duration);
+    }
+
+    let option = CreateSelectMenuOption::new(label, i.to_string());
+    if description.is_empty() {
+        option
+    } else {
+        option.description(description.chars().take(100).collect::<String>())
+    }
+}
@@ -0,0 +1,280 @@
+use crate::db::guilds::GuildStore;
+use crate::db::Database;
+pub use crate::models::queue::LoopMode;
+use crate::models::queue::SavedTrack;
+use crate::voice::cmds::{enqueue_info, get_metadata, source_for, TrackInfo, TrackInfoKey};
+use crate::voice::controls::{self, NowPlayingMessage};
+use crate::voice::filters::FilterHandle;
+use crate::voice::{autoplay, persist, radio};
+use reqwest::Client as HttpClient;
+use serenity::async_trait;
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use serenity::prelude::{Mutex, RwLock};
+use songbird::{
+    tracks::{PlayMode, TrackHandle},
+    Call, Event, EventContext, EventHandler, Songbird, TrackEvent,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, LazyLock},
+};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, warn};
+
+/// Playback state of each guild with an active call.
+static SESSIONS: LazyLock<RwLock<HashMap<GuildId, Arc<Mutex<Session>>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+#[derive(Default)]
+pub struct Session {
+    pub loop_mode: LoopMode,
+    /// The track that last started playing. Only this one goes back into the
+    /// queue in queue loop mode, not tracks removed before they ever played.
+    pub now_playing: Option<TrackHandle>,
+    /// Whether new tracks are interleaved so requesters take turns.
+    pub fair_queue: bool,
+    /// Who voted to skip the current track.
+    pub skip_votes: HashSet<UserId>,
+    /// Audio filter for every track in the call.
+    pub filter: FilterHandle,
+    pub(super) now_playing_message: Option<NowPlayingMessage>,
+    /// Keeps the on-air title of a playing radio station current.
+    stream_title_watcher: Option<JoinHandle<()>>,
+    events_registered: bool,
+}
+
+pub async fn get(guild_id: GuildId) -> Arc<Mutex<Session>> {
+    if let Some(session) = SESSIONS.read().await.get(&guild_id) {
+        return session.clone();
+    }
+    SESSIONS.write().await.entry(guild_id).or_default().clone()
+}
+
+pub async fn remove(guild_id: GuildId) {
+    SESSIONS.write().await.remove(&guild_id);
+}
+
+/// Switches the loop mode, applying it to the current track right away.
+pub async fn set_loop_mode(guild_id: GuildId, current: Option<TrackHandle>, mode: LoopMode) {
+    get(guild_id).await.lock().await.loop_mode = mode;
+    if let Some(track) = current {
+        let looped = match mode {
+            LoopMode::Track => track.enable_loop(),
+            LoopMode::Off | LoopMode::Queue => track.disable_loop(),
+        };
+        if let Err(why) = looped {
+            warn!("Err setting loop {:?}", why);
+        }
+    }
+}
+
+/// Applies the loop mode, records played tracks, autoplays related ones once
+/// the queue runs out and keeps the now-playing message current as tracks
+/// start and end.
+#[derive(Clone)]
+pub struct SessionEvents {
+    manager: Arc<Songbird>,
+    guild_id: GuildId,
+    db: Database,
+    guilds: Arc<GuildStore>,
+    http: Arc<Http>,
+    http_client: HttpClient,
+}
+
+impl SessionEvents {
+    pub fn new(
+        manager: Arc<Songbird>,
+        guild_id: GuildId,
+        db: Database,
+        guilds: Arc<GuildStore>,
+        http: Arc<Http>,
+        http_client: HttpClient,
+    ) -> Self {
+        Self {
+            manager,
+            guild_id,
+            db,
+            guilds,
+            http,
+            http_client,
+        }
+    }
+
+    pub async fn register_handler(&self, handler_lock: &Arc<Mutex<Call>>) {
+        let session = get(self.guild_id).await;
+        let mut session = session.lock().await;
+        if session.events_registered {
+            return;
+        }
+        session.events_registered = true;
+
+        let mut handler = handler_lock.lock().await;
+        handler.add_global_event(Event::Track(TrackEvent::Play), self.clone());
+        handler.add_global_event(Event::Track(TrackEvent::End), self.clone());
+    }
+
+    /// Puts a finished track back at the end of the queue, at the same volume.
+    async fn requeue(&self, info: TrackInfo, volume: f32) {
+        let Some(handler_lock) = self.manager.get(self.guild_id) else {
+            return;
+        };
+        let source = match source_for(self.http_client.clone(), &info) {
+            Ok(source) => source,
+            Err(why) => {
+                warn!("Not requeueing {}: {}", info.title, why);
+                return;
+            }
+        };
+        let mut handler = handler_lock.lock().await;
+        enqueue_info(&mut handler, self.guild_id, source, info, volume).await;
+    }
+
+    /// Whether a track that just finished should be followed by a related
+    /// one: it ran to its end without looping, autoplay is on and nothing else
+    /// is queued.
+    async fn should_autoplay(&self, track: &TrackHandle, info: &TrackInfo) -> bool {
+        if info.source == radio::RADIO_SOURCE || !info.is_replayable() {
+            return false;
+        }
+        if !self
+            .guilds
+            .get(self.guild_id)
+            .await
+            .is_some_and(|config| config.autoplay)
+        {
+            return false;
+        }
+        let Some(handler_lock) = self.manager.get(self.guild_id) else {
+            return false;
+        };
+        let handler = handler_lock.lock().await;
+        handler
+            .queue()
+            .current_queue()
+            .iter()
+            .all(|queued| queued.uuid() == track.uuid())
+    }
+}
+
+#[async_trait]
+impl EventHandler for SessionEvents {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(tracks) = ctx else {
+            return None;
+        };
+        let session = get(self.guild_id).await;
+
+        for (state, track) in tracks.iter() {
+            if state.playing == PlayMode::Play {
+                let is_radio = track
+                    .typemap()
+                    .read()
+                    .await
+                    .get::<TrackInfoKey>()
+                    .is_some_and(|info| info.source == radio::RADIO_SOURCE);
+                let mut session = session.lock().await;
+                let is_new = session
+                    .now_playing
+                    .as_ref()
+                    .is_none_or(|playing| playing.uuid() != track.uuid());
+                if is_new {
+                    let watcher = is_radio.then(|| {
+                        radio::watch_stream_title(
+                            (*track).clone(),
+                            self.http_client.clone(),
+                            self.http.clone(),
+                            self.manager.clone(),
+                            self.guild_id,
+                        )
+                    });
+                    if let Some(old) = std::mem::replace(&mut session.stream_title_watcher, watcher)
+                    {
+                        old.abort();
+                    }
+                }
+                session.now_playing = Some((*track).clone());
+                session.skip_votes.clear();
+                if session.loop_mode == LoopMode::Track {
+                    if let Err(why) = track.enable_loop() {
+                        warn!("Err looping track {:?}", why);
+                    }
+                }
+                continue;
+            }
+
+            let (was_playing, requeue, looping) = {
+                let mut session = session.lock().await;
+                let was_playing = session
+                    .now_playing
+                    .as_ref()
+                    .is_some_and(|playing| playing.uuid() == track.uuid());
+                if was_playing {
+                    session.now_playing = None;
+                    if let Some(watcher) = session.stream_title_watcher.take() {
+                        watcher.abort();
+                    }
+                }
+                (
+                    was_playing,
+                    was_playing && session.loop_mode == LoopMode::Queue,
+                    session.loop_mode != LoopMode::Off,
+                )
+            };
+            if !was_playing {
+                continue;
+            }
+            let Some(info) = track.typemap().read().await.get::<TrackInfoKey>().cloned() else {
+                continue;
+            };
+            record_play(&self.db, self.guild_id, &info);
+            if requeue {
+                debug!("Requeueing {}", info.title);
+                self.requeue(info, state.volume).await;
+            } else if state.playing == PlayMode::End
+                && !looping
+                && self.should_autoplay(track, &info).await
+            {
+                // Looking for a related track takes a while, so it happens
+                // away from the other track events.
+                tokio::spawn(autoplay::play_related(
+                    self.manager.clone(),
+                    self.db.clone(),
+                    self.http_client.clone(),
+                    self.guild_id,
+                    info,
+                    state.volume,
+                ));
+            }
+        }
+        controls::refresh(&self.http, &self.manager, self.guild_id).await;
+        persist::save(&self.db, &self.manager, self.guild_id).await;
+        None
+    }
+}
+
+/// Forgets the playing track ahead of stopping the queue, so its end isn't
+/// handled like a normal one. It still goes into the history.
+pub(super) async fn forget_current(db: &Database, guild_id: GuildId) {
+    let playing = {
+        let session = get(guild_id).await;
+        let mut session = session.lock().await;
+        if let Some(watcher) = session.stream_title_watcher.take() {
+            watcher.abort();
+        }
+        session.now_playing.take()
+    };
+    if let Some(track) = playing {
+        record_play(db, guild_id, &get_metadata(&track).await);
+    }
+}
+
+/// Adds a track that stopped playing to the guild's history.
+pub(super) fn record_play(db: &Database, guild_id: GuildId, info: &TrackInfo) {
+    if !info.is_replayable() {
+        return;
+    }
+    if let Err(why) = db.record_play(guild_id, &SavedTrack::from(info.clone())) {
+        error!("Err recording played track {:?}", why);
+    }
+}
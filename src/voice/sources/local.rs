@@ -0,0 +1,39 @@
+use crate::voice::files::{self, LIBRARY_PREFIX};
+use crate::voice::sources::{Resolved, TrackSource};
+use anyhow::{bail, Result};
+use serenity::async_trait;
+use songbird::input::{AuxMetadata, Compose, File};
+use tracing::error;
+
+/// Files in the local music library, by their path inside it.
+pub struct LocalFiles;
+
+#[async_trait]
+impl TrackSource for LocalFiles {
+    async fn resolve(&self, path: &str) -> Result<Resolved> {
+        if files::library_dir().is_none() {
+            bail!("There is no music library set up.");
+        }
+        let Some(full) = files::resolve(path) else {
+            bail!("There is nothing called `{}` in the library.", path);
+        };
+        if !full.is_file() || !files::is_audio_file(&full) {
+            bail!("That doesn't look like an audio file.");
+        }
+
+        let metadata = match File::new(full.clone()).create_async().await {
+            Ok(stream) => files::read_metadata(stream).await,
+            Err(why) => {
+                error!("Err opening library file {:?}", why);
+                AuxMetadata::default()
+            }
+        };
+        let path = files::library_path(&full).unwrap_or_default();
+        let name = full.file_name().unwrap_or_default().to_string_lossy();
+        let info = files::track_info(metadata, format!("{}{}", LIBRARY_PREFIX, path), &name);
+        Ok(Resolved {
+            info,
+            source: Box::new(File::new(full)),
+        })
+    }
+}
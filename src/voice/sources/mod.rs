@@ -0,0 +1,255 @@
+mod local;
+#[cfg(test)]
+mod memory;
+mod ytdl;
+
+use crate::voice::cmds::TrackInfo;
+use crate::voice::files::LIBRARY_PREFIX;
+use anyhow::Result;
+use reqwest::Client as HttpClient;
+use serenity::async_trait;
+use songbird::input::Compose;
+
+pub(super) use local::LocalFiles;
+#[cfg(test)]
+pub(super) use memory::{wav, MemorySource};
+pub(super) use ytdl::YtDlp;
+
+/// A track a query led to: what's known about it, and the input that streams
+/// it once it's queued.
+pub(super) struct Resolved {
+    pub info: TrackInfo,
+    pub source: Box<dyn Compose>,
+}
+
+/// Somewhere tracks come from, like yt-dlp or the music library.
+#[async_trait]
+pub(super) trait TrackSource: Send + Sync {
+    /// Finds the track a query stands for. The query comes without the prefix
+    /// that picked this source, and errors are meant to be shown to whoever
+    /// asked.
+    async fn resolve(&self, query: &str) -> Result<Resolved>;
+}
+
+/// Track sources by query prefix, falling back to one for queries without a
+/// known prefix.
+pub(super) struct Sources {
+    prefixed: Vec<(&'static str, Box<dyn TrackSource>)>,
+    fallback: Box<dyn TrackSource>,
+}
+
+impl Sources {
+    /// `file:` plays from the music library, `yt:` and `sc:` search YouTube and
+    /// SoundCloud and anything else goes to yt-dlp as a link or YouTube
+    /// search.
+    pub fn new(http_client: HttpClient) -> Self {
+        Self::with_fallback(YtDlp::youtube(http_client.clone()))
+            .register(LIBRARY_PREFIX, LocalFiles)
+            .register("yt:", YtDlp::youtube(http_client.clone()))
+            .register("sc:", YtDlp::soundcloud(http_client))
+    }
+
+    pub fn with_fallback(fallback: impl TrackSource + 'static) -> Self {
+        Self {
+            prefixed: Vec::new(),
+            fallback: Box::new(fallback),
+        }
+    }
+
+    pub fn register(mut self, prefix: &'static str, source: impl TrackSource + 'static) -> Self {
+        self.prefixed.push((prefix, Box::new(source)));
+        self
+    }
+
+    /// The source for a query, and the query with its prefix taken off.
+    pub fn select<'a>(&self, query: &'a str) -> (&dyn TrackSource, &'a str) {
+        let query = query.trim();
+        self.prefixed
+            .iter()
+            .find_map(|(prefix, source)| {
+                let rest = query.strip_prefix(prefix)?;
+                Some((source.as_ref(), rest.trim_start()))
+            })
+            .unwrap_or((self.fallback.as_ref(), query))
+    }
+
+    pub async fn resolve(&self, query: &str) -> Result<Resolved> {
+        let (source, query) = self.select(query);
+        source.resolve(query).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voice::cmds::{enqueue_info, get_metadata, song_embed, source_for};
+    use crate::voice::queue::{queue_page, QueueSummary};
+    use crate::voice::session::LoopMode;
+    use serde_json::Value;
+    use serenity::model::prelude::*;
+    use songbird::input::codecs::PROBE;
+    use songbird::input::AuxMetadata;
+    use songbird::Call;
+    use std::time::Duration;
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
+    use symphonia::core::meta::MetadataOptions;
+
+    fn sources() -> Sources {
+        Sources::with_fallback(MemorySource::default().with_track("fallback", 30))
+            .register("mem:", MemorySource::default().with_track("Intro", 90))
+    }
+
+    /// A guild of its own per test, since sessions are shared.
+    fn call(guild: u64) -> (Call, GuildId) {
+        let guild_id = GuildId::new(guild);
+        (Call::standalone(guild_id, UserId::new(1)), guild_id)
+    }
+
+    fn fields(embed: &Value) -> Vec<(&str, &str)> {
+        embed["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| {
+                (
+                    field["name"].as_str().unwrap(),
+                    field["value"].as_str().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn picks_source_by_prefix() {
+        let sources = sources();
+        let resolved = sources.resolve("mem: Intro").await.unwrap();
+        assert_eq!(resolved.info.title, "Intro");
+        let resolved = sources.resolve("fallback").await.unwrap();
+        assert_eq!(resolved.info.title, "fallback");
+    }
+
+    #[tokio::test]
+    async fn unknown_tracks_fail() {
+        assert!(sources().resolve("mem:Outro").await.is_err());
+        // Without the prefix the query goes to the fallback, which doesn't
+        // know the track either.
+        assert!(sources().resolve("Intro").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn resolved_input_decodes() {
+        let mut resolved = sources().resolve("mem:Intro").await.unwrap();
+        let stream = resolved.source.create().unwrap();
+        let input = MediaSourceStream::new(stream.input, MediaSourceStreamOptions::default());
+        let probed = PROBE
+            .format(
+                &stream.hint.unwrap_or_default(),
+                input,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .unwrap();
+        let params = &probed.format.default_track().unwrap().codec_params;
+        assert_eq!(params.channels.map(|channels| channels.count()), Some(2));
+        assert_eq!(params.n_frames, Some(90 * 48_000));
+    }
+
+    #[test]
+    fn expiring_and_missing_tracks_are_not_replayed() {
+        let track = |url: &str| {
+            TrackInfo::from(AuxMetadata {
+                source_url: Some(url.to_string()),
+                ..AuxMetadata::default()
+            })
+        };
+        let attachment = track("https://cdn.discordapp.com/attachments/1/2/song.mp3?ex=1");
+        assert!(!attachment.is_replayable());
+        assert!(track("https://www.youtube.com/watch?v=dQw4w9WgXcQ").is_replayable());
+
+        let gone = track("file:no/such/track.flac");
+        assert!(source_for(HttpClient::new(), &gone).is_err());
+    }
+
+    #[tokio::test]
+    async fn queues_tracks_in_order_with_their_info() {
+        let (mut call, guild_id) = call(24_001);
+        let sources = Sources::with_fallback(
+            MemorySource::default()
+                .with_track("One", 60)
+                .with_track("Two", 120)
+                .with_track("Three", 180),
+        );
+        for name in ["One", "Two", "Three"] {
+            let resolved = sources.resolve(name).await.unwrap();
+            enqueue_info(&mut call, guild_id, resolved.source, resolved.info, 0.5).await;
+        }
+
+        let mut queued = Vec::new();
+        for track in &call.queue().current_queue() {
+            let info = get_metadata(track).await;
+            queued.push((info.title, info.duration.map(|duration| duration.as_secs())));
+        }
+        assert_eq!(
+            queued,
+            [
+                ("One".to_string(), Some(60)),
+                ("Two".to_string(), Some(120)),
+                ("Three".to_string(), Some(180)),
+            ]
+        );
+        call.queue().stop();
+    }
+
+    #[tokio::test]
+    async fn queued_track_embed() {
+        let (mut call, guild_id) = call(24_002);
+        let resolved = sources().resolve("mem:Intro").await.unwrap();
+        let info = TrackInfo {
+            requester: Some(UserId::new(7)),
+            ..resolved.info
+        };
+        enqueue_info(&mut call, guild_id, resolved.source, info.clone(), 1.0).await;
+
+        let embed = serde_json::to_value(song_embed(&info, 1)).unwrap();
+        assert_eq!(embed["title"], "rauly.rs | Intro");
+        // Memory tracks have no web page to link to.
+        assert!(embed.get("url").is_none());
+        let fields = fields(&embed);
+        assert!(fields.contains(&("Song Duration", "1:30")));
+        assert!(fields.contains(&("Requested by", "<@7>")));
+        assert!(fields.contains(&("Source", "Memory")));
+        assert!(fields.contains(&("Position in Queue", "1")));
+        call.queue().stop();
+    }
+
+    #[tokio::test]
+    async fn queue_embed_lists_upcoming_tracks() {
+        let (mut call, guild_id) = call(24_003);
+        let sources = Sources::with_fallback(
+            MemorySource::default()
+                .with_track("Playing", 60)
+                .with_track("Next", 125),
+        );
+        for name in ["Playing", "Next"] {
+            let resolved = sources.resolve(name).await.unwrap();
+            enqueue_info(&mut call, guild_id, resolved.source, resolved.info, 1.0).await;
+        }
+
+        let mut infos = Vec::new();
+        for track in &call.queue().current_queue() {
+            infos.push(get_metadata(track).await);
+        }
+        let summary = QueueSummary {
+            loop_mode: LoopMode::Off,
+            fair_queue: false,
+            volume: 1.0,
+            elapsed: Duration::ZERO,
+        };
+        let embed = serde_json::to_value(queue_page(&infos, &summary, 0)).unwrap();
+        let description = embed["description"].as_str().unwrap();
+        assert!(description.contains("Playing"));
+        assert!(description.contains("**1**. Next `2:05`"));
+        call.queue().stop();
+    }
+}
@@ -0,0 +1,59 @@
+use crate::voice::cmds::TrackInfo;
+use crate::voice::sources::{Resolved, TrackSource};
+use anyhow::{anyhow, Result};
+use reqwest::Client as HttpClient;
+use serenity::async_trait;
+use songbird::input::{Compose, YoutubeDl};
+use tracing::{debug, error};
+
+/// yt-dlp, which plays links from most sites and searches one of them for
+/// anything else.
+pub struct YtDlp {
+    http_client: HttpClient,
+    /// yt-dlp's search prefix for the site searched, like `ytsearch1:`.
+    search: &'static str,
+}
+
+impl YtDlp {
+    pub fn youtube(http_client: HttpClient) -> Self {
+        Self {
+            http_client,
+            search: "ytsearch1:",
+        }
+    }
+
+    pub fn soundcloud(http_client: HttpClient) -> Self {
+        Self {
+            http_client,
+            search: "scsearch1:",
+        }
+    }
+}
+
+#[async_trait]
+impl TrackSource for YtDlp {
+    async fn resolve(&self, query: &str) -> Result<Resolved> {
+        let target = if query.starts_with("http") {
+            query.to_string()
+        } else {
+            format!("{}{}", self.search, query)
+        };
+        let mut ytdl = YoutubeDl::new(self.http_client.clone(), target);
+        let metadata = ytdl.aux_metadata().await.map_err(|why| {
+            error!("Err getting metadata {:?}", why);
+            anyhow!("Couldn't find anything for `{}`.", query)
+        })?;
+        debug!("metadata: {metadata:?}");
+
+        // Stream the track that was found, rather than searching again when
+        // it starts and maybe finding another.
+        let source = match &metadata.source_url {
+            Some(url) => YoutubeDl::new(self.http_client.clone(), url.clone()),
+            None => ytdl,
+        };
+        Ok(Resolved {
+            info: TrackInfo::from(metadata),
+            source: Box::new(source),
+        })
+    }
+}